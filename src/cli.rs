@@ -0,0 +1,286 @@
+//! The command line: which command to run, and the options it's run with.
+
+use std::path::PathBuf;
+
+use crate::manifest::Target;
+
+/// A command, with the arguments it was given.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    /// Creates a project in the current directory, named after it unless a name is given.
+    Init {
+        name: Option<String>,
+    },
+    /// Builds the given scripts, or every script of the project if none are given.
+    Build {
+        inputs: Vec<PathBuf>,
+    },
+    /// Builds like `Build`, then builds and sends again whenever the scripts change, until stopped.
+    Watch {
+        inputs: Vec<PathBuf>,
+    },
+    /// Runs the tests of the given scripts, or of the whole project if none are given.
+    BuildTest {
+        inputs: Vec<PathBuf>,
+    },
+    /// Adds a library to the project.
+    Add {
+        source: String,
+        name: Option<String>,
+    },
+    /// Decompiles template data, or a file of it.
+    Decompile {
+        input: String,
+    },
+    Version,
+    Recode,
+    Docs,
+    Help,
+}
+
+/// How much is printed besides the output and the errors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Verbosity {
+    Quiet,
+    Normal,
+    Verbose,
+}
+
+/// The options a command is run with. Every option can be given anywhere after the command.
+///   - Option<Target> `target`: Where built code lines go, instead of the target in the manifest
+///   - Option<PathBuf> `out`: The directory the `file` target writes to, instead of the one in the manifest
+///   - Verbosity `verbosity`: How much is printed
+///   - bool `color`: Whether the output is colored
+#[derive(Debug, Clone, PartialEq)]
+pub struct Options {
+    pub target: Option<Target>,
+    pub out: Option<PathBuf>,
+    pub verbosity: Verbosity,
+    pub color: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            target: None,
+            out: None,
+            verbosity: Verbosity::Normal,
+            color: true,
+        }
+    }
+}
+
+impl Options {
+    /// Prints a step of a command, e.g. `Building from `scripts` directory.`, unless `--quiet` is given.
+    pub fn status(&self, step: &str, message: impl AsRef<str>) {
+        if self.verbosity > Verbosity::Quiet {
+            eprintln!("\t{} {}", self.paint("32;1", step), message.as_ref());
+        }
+    }
+
+    /// Prints a detail of a command, only if `--verbose` is given.
+    pub fn detail(&self, message: impl AsRef<str>) {
+        if self.verbosity == Verbosity::Verbose {
+            eprintln!("\t{}", message.as_ref());
+        }
+    }
+
+    /// Wraps text in an ANSI style, e.g. `31` for red, unless `--no-color` is given.
+    pub fn paint(&self, style: &str, text: impl AsRef<str>) -> String {
+        if self.color {
+            format!("\x1b[{style}m{}\x1b[0m", text.as_ref())
+        } else {
+            text.as_ref().to_string()
+        }
+    }
+}
+
+/// Reads the command and its options from the arguments, without the name of the program.
+/// No command at all is `help`.
+pub fn parse(args: &[String]) -> Result<(Command, Options), String> {
+    let mut options = Options::default();
+    let mut positional = vec![];
+    let mut verbose = false;
+    let mut quiet = false;
+    let mut help = false;
+    let mut version = false;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        // `--out=dir` is the same as `--out dir`.
+        let (flag, inline) = match arg.split_once('=') {
+            Some((flag, value)) if arg.starts_with("--") => (flag, Some(value.to_string())),
+            _ => (arg.as_str(), None),
+        };
+        let mut value = |name: &str| {
+            inline
+                .clone()
+                .or_else(|| args.next().cloned())
+                .ok_or_else(|| format!("`{name}` needs a value"))
+        };
+        match flag {
+            "-o" | "--out" => options.out = Some(PathBuf::from(value("--out")?)),
+            "-t" | "--target" => options.target = Some(target(&value("--target")?)?),
+            "-v" | "--verbose" => verbose = true,
+            "-q" | "--quiet" => quiet = true,
+            "--no-color" => options.color = false,
+            "-h" | "--help" => help = true,
+            "-V" | "--version" => version = true,
+            "--" => positional.extend(args.by_ref().cloned()),
+            flag if flag.starts_with('-') && flag.len() > 1 => {
+                return Err(format!("unknown option `{flag}`"))
+            }
+            _ => positional.push(arg.clone()),
+        }
+    }
+    options.verbosity = match (verbose, quiet) {
+        (true, true) => return Err("`--verbose` and `--quiet` can't be used together".to_string()),
+        (true, false) => Verbosity::Verbose,
+        (false, true) => Verbosity::Quiet,
+        (false, false) => Verbosity::Normal,
+    };
+    if help {
+        return Ok((Command::Help, options));
+    }
+    if version {
+        return Ok((Command::Version, options));
+    }
+
+    let Some((command, arguments)) = positional.split_first() else {
+        return Ok((Command::Help, options));
+    };
+    let paths = || arguments.iter().map(PathBuf::from).collect::<Vec<_>>();
+    let command = match command.as_str() {
+        "init" => Command::Init {
+            name: at_most_one(command, arguments)?,
+        },
+        "build" => Command::Build { inputs: paths() },
+        // These were the ways of building single scripts before `build` took them.
+        "build-one" | "build-stdout" => {
+            if arguments.is_empty() {
+                return Err(format!(
+                    "`{command}` needs a script, e.g. `{command} scripts/join.bls`"
+                ));
+            }
+            let implied = if command == "build-one" {
+                Target::Recode
+            } else {
+                Target::Stdout
+            };
+            options.target = options.target.or(Some(implied));
+            Command::Build { inputs: paths() }
+        }
+        "watch" => Command::Watch { inputs: paths() },
+        "build-test" => Command::BuildTest { inputs: paths() },
+        "add" => match arguments {
+            [source] => Command::Add {
+                source: source.clone(),
+                name: None,
+            },
+            [source, name] => Command::Add {
+                source: source.clone(),
+                name: Some(name.clone()),
+            },
+            [] => return Err("`add` needs a project directory or a tarball, e.g. `add ../entity_utils` or `add vendor/entity_utils.tar.gz`".to_string()),
+            _ => return Err("`add` takes a path and an optional name".to_string()),
+        },
+        "decompile" => match at_most_one(command, arguments)? {
+            Some(input) => Command::Decompile { input },
+            None => return Err("`decompile` needs a template or a file containing one, e.g. `decompile templates.txt`".to_string()),
+        },
+        "version" | "recode" | "docs" | "help" => {
+            if let Some(argument) = arguments.first() {
+                return Err(format!("unexpected argument `{argument}` for `{command}`"));
+            }
+            match command.as_str() {
+                "version" => Command::Version,
+                "recode" => Command::Recode,
+                "docs" => Command::Docs,
+                _ => Command::Help,
+            }
+        }
+        _ => return Err(format!("unknown command `{command}`")),
+    };
+    Ok((command, options))
+}
+
+/// The name of a target, as in the manifest.
+fn target(name: &str) -> Result<Target, String> {
+    match name {
+        "recode" => Ok(Target::Recode),
+        "stdout" => Ok(Target::Stdout),
+        "file" => Ok(Target::File),
+        "codeclient" => Ok(Target::CodeClient),
+        _ => Err(format!(
+            "unknown target `{name}`, expected `recode`, `codeclient`, `stdout` or `file`"
+        )),
+    }
+}
+
+/// The single optional argument of a command.
+fn at_most_one(command: &str, arguments: &[String]) -> Result<Option<String>, String> {
+    match arguments {
+        [] => Ok(None),
+        [argument] => Ok(Some(argument.clone())),
+        [_, extra, ..] => Err(format!("unexpected argument `{extra}` for `{command}`")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_str(args: &str) -> Result<(Command, Options), String> {
+        parse(
+            &args
+                .split_whitespace()
+                .map(String::from)
+                .collect::<Vec<_>>(),
+        )
+    }
+
+    #[test]
+    fn build_takes_many_inputs_and_options_anywhere() {
+        let (command, options) =
+            parse_str("build -q a.bls --target=file b.bls --out build --no-color").unwrap();
+        assert_eq!(
+            command,
+            Command::Build {
+                inputs: vec![PathBuf::from("a.bls"), PathBuf::from("b.bls")]
+            }
+        );
+        assert_eq!(
+            options,
+            Options {
+                target: Some(Target::File),
+                out: Some(PathBuf::from("build")),
+                verbosity: Verbosity::Quiet,
+                color: false,
+            }
+        );
+    }
+
+    #[test]
+    fn old_build_commands_read_their_script() {
+        let (command, options) = parse_str("build-stdout scripts/join.bls").unwrap();
+        assert_eq!(
+            command,
+            Command::Build {
+                inputs: vec![PathBuf::from("scripts/join.bls")]
+            }
+        );
+        assert_eq!(options.target, Some(Target::Stdout));
+        assert!(parse_str("build-one").is_err());
+    }
+
+    #[test]
+    fn mistakes_are_errors() {
+        assert_eq!(parse_str("").unwrap().0, Command::Help);
+        assert!(parse_str("biuld").is_err());
+        assert!(parse_str("build --target nowhere").is_err());
+        assert!(parse_str("build --out").is_err());
+        assert!(parse_str("build --fast").is_err());
+        assert!(parse_str("build -v -q").is_err());
+        assert!(parse_str("init a b").is_err());
+    }
+}
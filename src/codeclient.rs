@@ -0,0 +1,247 @@
+//! A client for the WebSocket API of the CodeClient mod, which can give templates to the player
+//! or place them straight into the dev area of their plot.
+//!
+//! Every message is a text command. CodeClient answers `scopes` with `auth` once the player accepts
+//! with `/auth`, and a `place go` with `place done` once every template is placed.
+
+use std::{
+    fmt, io,
+    net::{TcpStream, ToSocketAddrs},
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+use tungstenite::{Message, WebSocket};
+
+use crate::output::Envelope;
+
+/// Where CodeClient listens.
+pub const CODECLIENT_ADDRESS: &str = "localhost:31375";
+
+/// How long to wait for CodeClient to connect.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long to wait for an answer, which can take a while since the player has to accept with `/auth`,
+/// and placing a lot of templates takes time.
+const ANSWER_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// What CodeClient does with built templates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum CodeClientMode {
+    /// Places them in the dev area of the plot, replacing the code lines with the same definitions.
+    #[default]
+    Place,
+    /// Gives them to the player as template items.
+    Give,
+}
+
+impl CodeClientMode {
+    /// The scopes the player is asked to allow.
+    fn scopes(self) -> &'static str {
+        match self {
+            CodeClientMode::Place => "movement write_code",
+            CodeClientMode::Give => "inventory",
+        }
+    }
+}
+
+/// Why templates couldn't go through CodeClient.
+#[derive(Debug)]
+pub enum CodeClientError {
+    /// CodeClient couldn't be reached, or the connection broke.
+    Connection(Box<tungstenite::Error>),
+    /// CodeClient answered something other than what was expected, e.g. `unauthed`.
+    Rejected(String),
+}
+
+impl fmt::Display for CodeClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CodeClientError::Connection(e) => write!(
+                f,
+                "couldn't reach CodeClient ({e}), is Minecraft running with it installed?"
+            ),
+            CodeClientError::Rejected(answer) => write!(f, "CodeClient answered `{answer}`"),
+        }
+    }
+}
+
+impl From<tungstenite::Error> for CodeClientError {
+    fn from(e: tungstenite::Error) -> Self {
+        CodeClientError::Connection(Box::new(e))
+    }
+}
+
+impl From<io::Error> for CodeClientError {
+    fn from(e: io::Error) -> Self {
+        CodeClientError::Connection(Box::new(e.into()))
+    }
+}
+
+/// A client connected to CodeClient, allowed by the player to do what its mode needs.
+pub struct CodeClient {
+    socket: WebSocket<TcpStream>,
+    mode: CodeClientMode,
+}
+
+impl CodeClient {
+    /// Connects to CodeClient and asks the player for the scopes the mode needs,
+    /// waiting until they accept with `/auth`.
+    pub fn connect(
+        address: impl ToSocketAddrs,
+        mode: CodeClientMode,
+    ) -> Result<CodeClient, CodeClientError> {
+        let address = address
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no address to connect to"))?;
+        let stream = TcpStream::connect_timeout(&address, CONNECT_TIMEOUT)?;
+        stream.set_read_timeout(Some(ANSWER_TIMEOUT))?;
+        let (socket, _) =
+            tungstenite::client(format!("ws://{address}"), stream).map_err(|e| match e {
+                tungstenite::HandshakeError::Failure(e) => e.into(),
+                tungstenite::HandshakeError::Interrupted(_) => {
+                    CodeClientError::Rejected("the handshake was interrupted".to_string())
+                }
+            })?;
+        let mut client = CodeClient { socket, mode };
+        client.command(format!("scopes {}", mode.scopes()))?;
+        client.expect("auth")?;
+        Ok(client)
+    }
+
+    /// Gives or places the templates, depending on the mode.
+    pub fn deliver(&mut self, templates: &[Envelope]) -> Result<(), CodeClientError> {
+        match self.mode {
+            CodeClientMode::Place => {
+                self.command("mode code")?;
+                self.command("place swap")?;
+                for template in templates {
+                    self.command(format!("place {}", template.code))?;
+                }
+                self.command("place go")?;
+                self.expect("place done")
+            }
+            CodeClientMode::Give => {
+                for template in templates {
+                    self.command(format!("give {}", template_item(template)))?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn command(&mut self, command: impl Into<String>) -> Result<(), CodeClientError> {
+        Ok(self.socket.send(Message::Text(command.into()))?)
+    }
+
+    /// Waits for an answer, which has to be `expected`.
+    fn expect(&mut self, expected: &str) -> Result<(), CodeClientError> {
+        loop {
+            match self.socket.read()? {
+                Message::Text(answer) if answer.trim() == expected => return Ok(()),
+                Message::Text(answer) => return Err(CodeClientError::Rejected(answer)),
+                Message::Close(_) => {
+                    return Err(CodeClientError::Rejected(
+                        "the connection was closed".to_string(),
+                    ))
+                }
+                // Pings are answered by the socket itself.
+                _ => continue,
+            }
+        }
+    }
+}
+
+/// The SNBT of a template item holding a template, for `give`.
+pub fn template_item(template: &Envelope) -> String {
+    let envelope = serde_json::to_string(template).expect("failed to serialize template");
+    let name = serde_json::json!({ "text": template.name, "italic": false }).to_string();
+    format!(
+        r#"{{Count:1b,id:"minecraft:ender_chest",tag:{{display:{{Name:{}}},PublicBukkitValues:{{"hypercube:codetemplatedata":{}}}}}}}"#,
+        snbt_string(&name),
+        snbt_string(&envelope)
+    )
+}
+
+/// A single quoted SNBT string.
+fn snbt_string(value: &str) -> String {
+    format!("'{}'", value.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{net::TcpListener, thread};
+
+    /// A mock CodeClient that accepts the scopes it's asked for, and answers `place go`.
+    /// Returns every command it got.
+    fn mock_codeclient() -> (String, thread::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let handle = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut socket = tungstenite::accept(stream).unwrap();
+            let mut commands = vec![];
+            while let Ok(Message::Text(command)) = socket.read() {
+                if command.starts_with("scopes ") {
+                    socket.send(Message::Text("auth".to_string())).unwrap();
+                } else if command == "place go" {
+                    socket
+                        .send(Message::Text("place done".to_string()))
+                        .unwrap();
+                }
+                commands.push(command);
+            }
+            commands
+        });
+        (address, handle)
+    }
+
+    fn envelope(name: &str) -> Envelope {
+        Envelope {
+            author: "Endistic".to_string(),
+            name: name.to_string(),
+            version: 1,
+            code: format!("code of {name}"),
+        }
+    }
+
+    #[test]
+    fn templates_are_placed() {
+        let (address, codeclient) = mock_codeclient();
+        let mut client = CodeClient::connect(address, CodeClientMode::Place).unwrap();
+        client
+            .deliver(&[
+                envelope("Player Event » Join"),
+                envelope("Function » greet"),
+            ])
+            .unwrap();
+        drop(client);
+        assert_eq!(
+            codeclient.join().unwrap(),
+            vec![
+                "scopes movement write_code",
+                "mode code",
+                "place swap",
+                "place code of Player Event » Join",
+                "place code of Function » greet",
+                "place go",
+            ]
+        );
+    }
+
+    #[test]
+    fn templates_are_given() {
+        let (address, codeclient) = mock_codeclient();
+        let mut client = CodeClient::connect(address, CodeClientMode::Give).unwrap();
+        client.deliver(&[envelope("Function » it's")]).unwrap();
+        drop(client);
+        let commands = codeclient.join().unwrap();
+        assert_eq!(commands[0], "scopes inventory");
+        assert!(commands[1].starts_with(r#"give {Count:1b,id:"minecraft:ender_chest""#));
+        assert!(commands[1].contains(r#"'{"italic":false,"text":"Function » it\'s"}'"#));
+        assert!(commands[1].contains(r#""code":"code of Function » it\'s""#));
+    }
+}
@@ -0,0 +1,492 @@
+use std::collections::HashMap;
+
+use lazy_static::lazy_static;
+use serde::Deserialize;
+
+use super::item_data::ItemData;
+
+/// The raw action dump, exported from DF with `/dumpactioninfo`.
+static ACTION_DUMP_JSON: &str = include_str!("../../backend/action_dump.json");
+
+lazy_static! {
+    /// The parsed action dump. This is only deserialized the first time it's used.
+    pub static ref ACTION_DUMP: ActionDump = {
+        let mut dump: ActionDump =
+            serde_json::from_str(ACTION_DUMP_JSON).expect("failed to parse action_dump.json");
+        dump.build_registry();
+        dump
+    };
+}
+
+/// Represents the parts of DF's action dump that Blackstone uses.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ActionDump {
+    pub codeblocks: Vec<CodeBlock>,
+    pub actions: Vec<Action>,
+    pub game_values: Vec<GameValue>,
+    pub sounds: Vec<Sound>,
+    pub potions: Vec<Potion>,
+    pub particles: Vec<Particle>,
+    /// Indices into `actions`, keyed by code block identifier and then by lowercased action name or alias.
+    #[serde(skip)]
+    registry: HashMap<String, HashMap<String, usize>>,
+}
+
+/// A type of code block (e.g. Player Action or If Variable).
+///   - String `name`: The display name of the block, e.g. `PLAYER ACTION`
+///   - String `identifier`: The id of the block in templates, e.g. `player_action`
+#[derive(Debug, Deserialize)]
+pub struct CodeBlock {
+    pub name: String,
+    pub identifier: String,
+}
+
+/// A single action of a code block (e.g. SendMessage or Join).
+///   - String `name`: The name of the action in templates
+///   - String `codeblock_name`: The display name of the block this action belongs to
+///   - Vec<Tag> `tags`: The block tags of this action
+///   - Vec<String> `aliases`: Older names for this action that DF still accepts
+///   - Icon `icon`: The menu icon of the action, which lists its arguments
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Action {
+    pub name: String,
+    pub codeblock_name: String,
+    pub tags: Vec<Tag>,
+    pub aliases: Vec<String>,
+    pub icon: Icon,
+}
+
+/// A block tag of an action (e.g. Alignment Mode of SendMessage).
+///   - String `name`: The name of the tag
+///   - Vec<TagOption> `options`: The options the tag can be set to
+///   - String `default_option`: The option DF uses when the tag is missing
+///   - i32 `slot`: The chest slot the tag goes in
+///   - Vec<String> `aliases`: Older names for this tag that DF still accepts
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Tag {
+    pub name: String,
+    pub options: Vec<TagOption>,
+    pub default_option: String,
+    pub slot: i32,
+    pub aliases: Vec<String>,
+}
+
+/// A single option of a block tag (e.g. Centered).
+///   - String `name`: The name of the option
+///   - Vec<String> `aliases`: Older names for this option that DF still accepts
+#[derive(Debug, Deserialize)]
+pub struct TagOption {
+    pub name: String,
+    pub aliases: Vec<String>,
+}
+
+/// A game value (e.g. Current Health or Event Block Location).
+///   - Icon `icon`: The menu icon of the game value. Its name is used in templates.
+///   - Vec<String> `aliases`: Older names for this game value that DF still accepts
+#[derive(Debug, Deserialize)]
+pub struct GameValue {
+    pub icon: Icon,
+    pub aliases: Vec<String>,
+}
+
+/// A sound (e.g. Creeper Death).
+///   - String `sound`: The id of the sound, e.g. `ENTITY_CREEPER_DEATH`
+///   - Icon `icon`: The menu icon of the sound. Its name is used in templates.
+#[derive(Debug, Deserialize)]
+pub struct Sound {
+    pub sound: String,
+    pub icon: Icon,
+}
+
+/// A potion effect (e.g. Speed).
+///   - String `potion`: The id of the effect, e.g. `SPEED`
+///   - Icon `icon`: The menu icon of the effect. Its name is used in templates.
+#[derive(Debug, Deserialize)]
+pub struct Potion {
+    pub potion: String,
+    pub icon: Icon,
+}
+
+/// A particle (e.g. Sweep Attack).
+///   - String `particle`: The id of the particle, e.g. `SWEEP_ATTACK`
+///   - Icon `icon`: The menu icon of the particle. Its name is used in templates.
+///   - Vec<String> `fields`: The extra fields the particle uses, e.g. `Size` and `Size Variation`
+#[derive(Debug, Deserialize)]
+pub struct Particle {
+    pub particle: String,
+    pub icon: Icon,
+    pub fields: Vec<String>,
+}
+
+/// The menu icon of an action or value.
+///   - String `name`: The display name of the icon
+///   - Option<Vec<Argument>> `arguments`: The arguments of an action, if it documents them
+#[derive(Debug, Deserialize)]
+pub struct Icon {
+    pub name: String,
+    pub arguments: Option<Vec<Argument>>,
+}
+
+/// An entry in the argument list of an action.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum Argument {
+    /// A parameter of the action.
+    ///   - ArgumentType `kind`: The type of value it accepts
+    ///   - bool `plural`: Whether it accepts multiple values
+    ///   - bool `optional`: Whether it can be left out
+    Parameter {
+        #[serde(rename = "type")]
+        kind: ArgumentType,
+        plural: bool,
+        optional: bool,
+    },
+    /// A line of text between parameters. `OR` separates different ways of calling the action.
+    Text { text: String },
+}
+
+/// The type of value a parameter accepts.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ArgumentType {
+    Number,
+    Text,
+    Location,
+    Vector,
+    Sound,
+    Potion,
+    Particle,
+    Item,
+    Block,
+    BlockTag,
+    Projectile,
+    SpawnEgg,
+    EntityType,
+    Vehicle,
+    List,
+    Dict,
+    Variable,
+    AnyType,
+    /// The action takes no arguments in this form.
+    None,
+    #[serde(other)]
+    Other,
+}
+
+/// Why a list of arguments doesn't fit an action.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ArgumentError {
+    /// The argument at `index` can't be used where an `expected` is needed.
+    WrongType {
+        index: usize,
+        expected: ArgumentType,
+    },
+    /// More arguments were given than the action accepts.
+    TooMany { max: usize },
+}
+
+impl ActionDump {
+    /// Indexes every action by its code block identifier and name for [`ActionDump::find_action`].
+    fn build_registry(&mut self) {
+        let identifiers = self
+            .codeblocks
+            .iter()
+            .map(|codeblock| (codeblock.name.as_str(), codeblock.identifier.as_str()))
+            .collect::<HashMap<_, _>>();
+        for (index, action) in self.actions.iter().enumerate() {
+            let Some(identifier) = identifiers.get(action.codeblock_name.as_str()) else {
+                continue;
+            };
+            let actions = self.registry.entry(identifier.to_string()).or_default();
+            actions.insert(action.name.to_lowercase(), index);
+            for alias in &action.aliases {
+                actions.entry(alias.to_lowercase()).or_insert(index);
+            }
+        }
+    }
+
+    /// Gets all actions belonging to the code block with the given identifier.
+    pub fn actions_of<'a>(&'a self, block: &str) -> impl Iterator<Item = &'a Action> {
+        let name = self
+            .codeblocks
+            .iter()
+            .find(|codeblock| codeblock.identifier == block)
+            .map(|codeblock| codeblock.name.as_str());
+        self.actions
+            .iter()
+            .filter(move |action| Some(action.codeblock_name.as_str()) == name)
+    }
+
+    /// Finds an action by its code block identifier and name.
+    /// Names are compared case-insensitively, and aliases are also checked.
+    pub fn find_action(&self, block: &str, name: &str) -> Option<&Action> {
+        let index = self.registry.get(block)?.get(&name.to_lowercase())?;
+        self.actions.get(*index)
+    }
+
+    /// Finds the action of a code block with the name closest to the given one, for "did you mean" suggestions.
+    pub fn suggest_action(&self, block: &str, name: &str) -> Option<&Action> {
+        let name = name.to_lowercase();
+        self.actions_of(block)
+            .map(|action| (edit_distance(&action.name.to_lowercase(), &name), action))
+            .filter(|(distance, _)| *distance <= (name.len() / 3).max(2))
+            .min_by_key(|(distance, _)| *distance)
+            .map(|(_, action)| action)
+    }
+
+    /// Finds a sound by its name (e.g. `Creeper Death`) or id (e.g. `ENTITY_CREEPER_DEATH`).
+    /// Returns the closest name as a suggestion if there's no such sound.
+    pub fn find_sound(&self, name: &str) -> Result<&Sound, Option<&str>> {
+        find_named(&self.sounds, name, |sound| [&sound.icon.name, &sound.sound])
+    }
+
+    /// Finds a potion effect by its name (e.g. `Resistance`) or id (e.g. `DAMAGE_RESISTANCE`).
+    /// Returns the closest name as a suggestion if there's no such effect.
+    pub fn find_potion(&self, name: &str) -> Result<&Potion, Option<&str>> {
+        find_named(&self.potions, name, |potion| {
+            [&potion.icon.name, &potion.potion]
+        })
+    }
+
+    /// Finds a particle by its name (e.g. `Sweep Attack`) or id (e.g. `SWEEP_ATTACK`).
+    /// Returns the closest name as a suggestion if there's no such particle.
+    pub fn find_particle(&self, name: &str) -> Result<&Particle, Option<&str>> {
+        find_named(&self.particles, name, |particle| {
+            [&particle.icon.name, &particle.particle]
+        })
+    }
+
+    /// Finds a game value by name, e.g. `EventBlockLocation` for `Event Block Location`.
+    /// Spaces and punctuation are ignored, names are compared case-insensitively, and aliases are also checked.
+    pub fn find_game_value(&self, name: &str) -> Option<&GameValue> {
+        let name = simplify_name(name);
+        self.game_values.iter().find(|value| {
+            simplify_name(&value.icon.name) == name
+                || value
+                    .aliases
+                    .iter()
+                    .any(|alias| simplify_name(alias) == name)
+        })
+    }
+}
+
+impl Action {
+    /// Checks that the given arguments fit one of the ways this action can be called.
+    /// Actions that don't document their arguments accept anything.
+    pub fn check_arguments(&self, args: &[ItemData]) -> Result<(), ArgumentError> {
+        let Some(arguments) = &self.icon.arguments else {
+            return Ok(());
+        };
+        let mut best: Option<(usize, ArgumentError)> = None;
+        for overload in arguments
+            .split(|argument| matches!(argument, Argument::Text { text } if text.trim() == "OR"))
+        {
+            match check_overload(overload, args) {
+                Ok(()) => return Ok(()),
+                Err((matched, err)) => {
+                    if best.as_ref().is_none_or(|(most, _)| matched > *most) {
+                        best = Some((matched, err));
+                    }
+                }
+            }
+        }
+        match best {
+            Some((_, err)) => Err(err),
+            None => Ok(()),
+        }
+    }
+
+    /// Finds a tag of this action by name, e.g. `alignment` or `alignmentMode` for `Alignment Mode`.
+    /// Spaces and punctuation are ignored, and an unambiguous start of the name is also accepted.
+    pub fn find_tag(&self, name: &str) -> Option<&Tag> {
+        let name = simplify_name(name);
+        let exact = self.tags.iter().find(|tag| {
+            simplify_name(&tag.name) == name
+                || tag.aliases.iter().any(|alias| simplify_name(alias) == name)
+        });
+        if exact.is_some() {
+            return exact;
+        }
+        let mut prefixed = self
+            .tags
+            .iter()
+            .filter(|tag| simplify_name(&tag.name).starts_with(&name));
+        match (prefixed.next(), prefixed.next()) {
+            (Some(tag), None) => Some(tag),
+            _ => None,
+        }
+    }
+}
+
+impl Tag {
+    /// Finds an option of this tag by name. Spaces and punctuation are ignored.
+    pub fn find_option(&self, name: &str) -> Option<&TagOption> {
+        let name = simplify_name(name);
+        self.options.iter().find(|option| {
+            simplify_name(&option.name) == name
+                || option
+                    .aliases
+                    .iter()
+                    .any(|alias| simplify_name(alias) == name)
+        })
+    }
+}
+
+impl ArgumentType {
+    /// Whether a value can be passed where this type is expected.
+    /// Variables and game values are only known at runtime, so they fit anywhere except a Variable parameter.
+    pub fn accepts(&self, data: &ItemData) -> bool {
+        match (self, data) {
+            (Self::None, _) => false,
+            (Self::Variable, ItemData::Variable { .. }) => true,
+            (Self::Variable, _) => false,
+            (_, ItemData::Variable { .. } | ItemData::GameValue { .. }) => true,
+            (Self::AnyType | Self::Other, _) => true,
+            (Self::Number, ItemData::Number { .. }) => true,
+            (Self::Text | Self::BlockTag, ItemData::Text { .. }) => true,
+            (Self::Location, ItemData::Location { .. }) => true,
+            (Self::Vector, ItemData::Vector { .. }) => true,
+            (Self::Sound, ItemData::Sound { .. }) => true,
+            (Self::Potion, ItemData::Potion { .. }) => true,
+            (Self::Particle, ItemData::Particle { .. }) => true,
+            (
+                Self::Item
+                | Self::Block
+                | Self::Projectile
+                | Self::SpawnEgg
+                | Self::EntityType
+                | Self::Vehicle,
+                ItemData::VanillaItem { .. },
+            ) => true,
+            (Self::Block | Self::EntityType, ItemData::Text { .. }) => true,
+            _ => false,
+        }
+    }
+
+    /// The name of this type in error messages.
+    pub fn describe(&self) -> &'static str {
+        match self {
+            Self::Number => "Number",
+            Self::Text => "Text",
+            Self::Location => "Location",
+            Self::Vector => "Vector",
+            Self::Sound => "Sound",
+            Self::Potion => "Potion",
+            Self::Particle => "Particle",
+            Self::Item => "Item",
+            Self::Block => "Block",
+            Self::BlockTag => "Block Tag",
+            Self::Projectile => "Projectile",
+            Self::SpawnEgg => "Spawn Egg",
+            Self::EntityType => "Entity Type",
+            Self::Vehicle => "Vehicle",
+            Self::List => "List",
+            Self::Dict => "Dictionary",
+            Self::Variable => "Variable",
+            Self::AnyType => "value",
+            Self::None => "nothing",
+            Self::Other => "value",
+        }
+    }
+}
+
+/// Matches arguments against one way of calling an action.
+/// On failure, also outputs how many arguments matched, so the closest overload can be reported.
+fn check_overload(overload: &[Argument], args: &[ItemData]) -> Result<(), (usize, ArgumentError)> {
+    let mut index = 0;
+    let mut max = 0;
+    let mut rejected: Option<(usize, ArgumentType)> = None;
+    for argument in overload {
+        let Argument::Parameter {
+            kind,
+            plural,
+            optional,
+        } = argument
+        else {
+            continue;
+        };
+        if *kind == ArgumentType::None {
+            continue;
+        }
+        max += 1;
+        let start = index;
+        while index < args.len() && kind.accepts(&args[index]) {
+            index += 1;
+            if !plural {
+                break;
+            }
+        }
+        if index < args.len() && (*plural || index == start) {
+            if rejected.is_none_or(|(at, _)| at != index) {
+                rejected = Some((index, *kind));
+            }
+            if index == start && !optional {
+                break;
+            }
+        }
+    }
+    if index == args.len() {
+        return Ok(());
+    }
+    match rejected {
+        Some((at, expected)) if at == index => {
+            Err((index, ArgumentError::WrongType { index, expected }))
+        }
+        _ => Err((index, ArgumentError::TooMany { max })),
+    }
+}
+
+/// Finds an entry by any of its names, ignoring case, spaces and punctuation.
+/// The first name of each entry is the one suggested when nothing matches.
+fn find_named<'a, T>(
+    entries: &'a [T],
+    name: &str,
+    names: impl Fn(&'a T) -> [&'a String; 2],
+) -> Result<&'a T, Option<&'a str>> {
+    let name = simplify_name(name);
+    if let Some(found) = entries
+        .iter()
+        .find(|entry| names(entry).iter().any(|n| simplify_name(n) == name))
+    {
+        return Ok(found);
+    }
+    Err(entries
+        .iter()
+        .map(|entry| names(entry)[0].as_str())
+        .map(|n| (edit_distance(&simplify_name(n), &name), n))
+        .filter(|(distance, _)| *distance <= (name.len() / 3).max(2))
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, n)| n))
+}
+
+/// The Levenshtein distance between two strings.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut row = (0..=b.len()).collect::<Vec<_>>();
+    for (i, ca) in a.chars().enumerate() {
+        let mut previous = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let current = row[j + 1];
+            row[j + 1] = if ca == *cb {
+                previous
+            } else {
+                1 + previous.min(row[j]).min(row[j + 1])
+            };
+            previous = current;
+        }
+    }
+    row[b.len()]
+}
+
+/// Lowercases a name and strips anything that isn't alphanumeric,
+/// so `X-Coordinate` and `xCoordinate` compare equal.
+pub(crate) fn simplify_name(name: &str) -> String {
+    name.chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
@@ -1,6 +1,9 @@
+use chumsky::span::SimpleSpan;
+
 use super::{
     item::Item,
     misc::{BracketDirection, BracketType},
+    template::{CodeBlock, TemplateArguments, TemplateBlock},
 };
 
 /// Represents a literal block of code in a DF code line.
@@ -30,19 +33,45 @@ pub enum Block<'a> {
     /// Defines a function definition
     ///   - &'a str `block`: The associated block
     ///   - &'a str `data`: Associated data (name, etc.)
-    FunctionDefinition { block: &'a str, data: String },
+    ///   - Vec<String> `docs`: The lines of the `///` doc comments above the definition
+    ///   - SimpleSpan `span`: Where the name was written, for error reporting
+    FunctionDefinition {
+        block: &'a str,
+        data: String,
+        docs: Vec<String>,
+        span: SimpleSpan,
+    },
     /// Defines a process definition
     ///   - &'a str `block`: The associated block
     ///   - &'a str `data`: Associated data (name, etc.)
-    ProcessDefinition { block: &'a str, data: String },
+    ///   - Vec<String> `docs`: The lines of the `///` doc comments above the definition
+    ///   - SimpleSpan `span`: Where the name was written, for error reporting
+    ProcessDefinition {
+        block: &'a str,
+        data: String,
+        docs: Vec<String>,
+        span: SimpleSpan,
+    },
     /// Defines a call to a given function
     ///   - &'a str `block`: The associated block
     ///   - &'a str `data`: Associated data (name, etc.)
-    FunctionCall { block: &'a str, data: String },
-    /// Defines a call to a given function
+    ///   - SimpleSpan `span`: Where the name was written, for error reporting
+    FunctionCall {
+        block: &'a str,
+        data: String,
+        span: SimpleSpan,
+    },
+    /// Defines a call to a given process
     ///   - &'a str `block`: The associated block
     ///   - &'a str `data`: Associated data (name, etc.)
-    ProcessCall { block: &'a str, data: String },
+    ///   - SimpleSpan `span`: Where the name was written, for error reporting
+    ///   - Vec<Item> `items`: Its Local Variables and Target Mode tags
+    ProcessCall {
+        block: &'a str,
+        data: String,
+        span: SimpleSpan,
+        items: Vec<Item>,
+    },
     /// Defines a bracket block (piston)
     ///   - BracketDirection `direct` - the direction of the bracket (opening or closing)
     ///   - BracketType `type` - the type of the bracket (Normal/Piston or Repeat/Sticky Piston)
@@ -50,15 +79,39 @@ pub enum Block<'a> {
         direct: BracketDirection,
         typ: BracketType,
     },
+    /// Defines a test, which is run by `build-test` and never sent to DF
+    ///   - String `name`: The name of the test
+    ///   - SimpleSpan `span`: Where the name was written, for error reporting
+    TestDefinition { name: String, span: SimpleSpan },
+    /// Defines an assertion in a test
+    ///   - Assertion `assertion`: What has to be true when the assertion is reached
+    ///   - SimpleSpan `span`: Where the assertion was written, for error reporting
+    Assert {
+        assertion: Assertion<'a>,
+        span: SimpleSpan,
+    },
 }
 
-static START_PROCESS_DEFAULT: &str = include_str!("../../backend/static/START_PROCESS_ITEMS");
+/// What an `assert` checks.
+#[derive(Debug, Clone)]
+pub enum Assertion<'a> {
+    /// `assert var ...`: An If Variable block, whose condition has to be met.
+    Condition(Box<Block<'a>>),
+    /// `assert sent("...")`: The player has to have been sent this message.
+    Sent(String),
+}
 
 #[allow(dead_code, unused)]
 impl Block<'_> {
     /// converts self to a workable json String
     pub fn to_json(&self) -> String {
-        match self {
+        serde_json::to_string(&self.to_template()).expect("failed to serialize block")
+    }
+
+    /// Converts self to the block DF stores in a template.
+    /// Returns None for tests and assertions, which are only run by the interpreter and never sent to DF.
+    pub fn to_template(&self) -> Option<TemplateBlock> {
+        let block = match self {
             Block::Code {
                 block,
                 items,
@@ -67,39 +120,47 @@ impl Block<'_> {
                 target,
                 inverted,
                 sub_action,
-            } => {
-                let mut items_str = String::new();
-                for item in items {
-                    items_str.push_str(item.to_json().as_str());
-                    items_str.push(',');
-                }
-                items_str.pop();
-                format!(
-                    r#"{{"id":"block","block":"{block}","args":{{"items":[{items_str}]}},"action":"{action}","target":"{target}","inverted":"{inverted}","data":"{data}","subAction":"{sub_action}"}}"#
-                )
-            }
-            Block::EventDefinition { block, action } => format!(
-                r#"{{"id":"block","block":"{block}","action":"{action}","args":{{"items":[]}}}}"#
-            ),
-            Block::Bracket { direct, typ } => format!(
-                r#"{{"id":"bracket","direct":"{}","type":"{}"}}"#,
-                direct.to_json(),
-                typ.to_json()
-            ),
-            Block::FunctionDefinition { block, data } => {
-                format!(
-                    r#"{{"id":"block","block":"{block}","args":{{"items":[]}},"data":"{data}"}}"#
-                )
-            }
-            Block::FunctionCall { block, data } => {
-                format!(r#"{{"id":"block","block":"{block}","data":"{data}"}}"#)
-            }
-            Block::ProcessDefinition { block, data } => format!(
-                r#"{{"id":"block","block":"{block}","args":{{"items":[]}},"data":"{data}"}}"#
-            ),
-            Block::ProcessCall { block, data } => format!(
-                r#"{{"id":"block","block":"{block}","data":"{data}","args":{{"items":[{START_PROCESS_DEFAULT}]}}}}"#
-            ),
-        }
+            } => TemplateBlock::Block(CodeBlock {
+                block: block.to_string(),
+                args: Some(TemplateArguments {
+                    items: items.iter().filter_map(Item::to_template).collect(),
+                }),
+                action: Some(action.clone()),
+                target: Some(target.to_string()),
+                inverted: Some(inverted.to_string()),
+                data: Some(data.to_string()),
+                sub_action: Some(sub_action.clone()),
+            }),
+            Block::EventDefinition { block, action } => TemplateBlock::Block(CodeBlock {
+                block: block.to_string(),
+                args: Some(TemplateArguments::default()),
+                action: Some(action.clone()),
+                ..Default::default()
+            }),
+            Block::Bracket { direct, typ } => TemplateBlock::Bracket {
+                direct: direct.clone(),
+                typ: typ.clone(),
+            },
+            Block::FunctionDefinition { block, data, .. }
+            | Block::FunctionCall { block, data, .. }
+            | Block::ProcessDefinition { block, data, .. } => TemplateBlock::Block(CodeBlock {
+                block: block.to_string(),
+                args: Some(TemplateArguments::default()),
+                data: Some(data.clone()),
+                ..Default::default()
+            }),
+            Block::ProcessCall {
+                block, data, items, ..
+            } => TemplateBlock::Block(CodeBlock {
+                block: block.to_string(),
+                args: Some(TemplateArguments {
+                    items: items.iter().filter_map(Item::to_template).collect(),
+                }),
+                data: Some(data.clone()),
+                ..Default::default()
+            }),
+            Block::TestDefinition { .. } | Block::Assert { .. } => return None,
+        };
+        Some(block)
     }
 }
@@ -1,6 +1,6 @@
-use crate::codegen::misc::VariableScope;
+use crate::error::{CompileError, ErrorCode};
 
-use super::item_data::ItemData;
+use super::{item_data::ItemData, template::TemplateSlot};
 
 #[derive(Debug, Clone, PartialEq, Default)]
 #[allow(dead_code, unused)]
@@ -10,34 +10,17 @@ pub struct Item {
     pub item: ItemData,
 }
 
-/*
-"item": {
-              "id": "txt",
-              "data": {
-                "name": "How is everyone?"
-              }
-            },
-            "slot": 1
- */
 #[allow(dead_code, unused)]
-#[allow(clippy::wrong_self_convention)] // we want to_json for consistency
 impl Item {
-    pub fn to_json(&self) -> String {
-        format!(
-            r#"{{"item":{{"id":"{}",{}}},"slot":{}}}"#,
-            self.id,
-            self.item.to_json(),
-            self.slot
-        )
+    /// Converts self to a filled chest slot. Returns None if the item has no data.
+    pub fn to_template(&self) -> Option<TemplateSlot> {
+        Some(TemplateSlot {
+            item: self.item.to_template()?,
+            slot: self.slot,
+        })
     }
-    pub fn from_strs(
-        from: String,
-        full_line: String,
-        line_number: i32,
-        raw_file: String,
-    ) -> Vec<Self> {
-        let mut out = Vec::new();
-
+    /// Turns a comma separated list of values into items, e.g. `"hi", 5, vanilla_item![stone:3]`.
+    pub fn from_strs(from: String) -> Result<Vec<Self>, CompileError> {
         let mut in_string = false;
         let mut builder = String::new();
         let mut split: Vec<String> = Vec::new();
@@ -54,62 +37,48 @@ impl Item {
         }
         split.push(builder.trim().to_string());
 
-        for (slot, arg) in split.into_iter().enumerate() {
-            out.push(Item::from_str(
-                arg.to_string(),
-                slot.try_into().expect("failed to i32"),
-                full_line.clone(),
-                line_number,
-                raw_file.clone(),
-            ));
-        }
-        out
+        split
+            .into_iter()
+            .enumerate()
+            .map(|(slot, arg)| Item::from_str(arg, slot.try_into().expect("failed to i32")))
+            .collect()
     }
-    pub fn from_str(
-        from: String,
-        slot: i32,
-        full_line: String,
-        line_number: i32,
-        raw_file: String,
-    ) -> Self {
+
+    /// Turns a single value into an item in the given slot.
+    pub fn from_str(from: String, slot: i32) -> Result<Self, CompileError> {
         if from.starts_with('\"') && from.ends_with('\"') {
             let from = from.replace('\"', "");
-            return Item {
+            return Ok(Item {
                 id: "txt".to_string(),
                 slot,
                 item: ItemData::Text { data: from },
-            };
+            });
         }
         if let Ok(v) = from.parse::<f32>() {
-            return Item {
+            return Ok(Item {
                 id: "num".to_string(),
                 slot,
                 item: ItemData::Number { data: v },
-            };
+            });
         }
         if from.starts_with("vanilla_item![") && from.ends_with(']') {
             let from = from.replace("vanilla_item![", "").replace(']', "");
-            let split = from.split(':').collect::<Vec<_>>();
-            if split.len() < 2 {
-                // errors::throw_error("not enough arguments in `vanilla_item!` statement", &full_line, &raw_file, line_number)
-            }
-            let id = split.first().expect("failed to get id");
-            let count = split.get(1).expect("failed to get count");
-            let datas = format!(r#"{{Count:{count}b,DF_NBT:3120,id:\"minecraft:{id}\"}}"#);
-            return Item {
+            let [id, count] = from.split(':').collect::<Vec<_>>()[..] else {
+                return Err(CompileError::new(
+                    ErrorCode::InvalidItem,
+                    format!("`vanilla_item!` needs an id and a count, e.g. `vanilla_item![stone:1]`, found `{from}`"),
+                ));
+            };
+            let datas = format!(r#"{{Count:{count}b,DF_NBT:3120,id:"minecraft:{id}"}}"#);
+            return Ok(Item {
                 id: "item".to_string(),
                 slot,
                 item: ItemData::VanillaItem { data: datas },
-            };
-        }
-        println!("bad item from string {from} to item");
-        Item {
-            id: "var".to_string(),
-            slot,
-            item: ItemData::Variable {
-                scope: VariableScope::Unsaved,
-                name: from,
-            },
+            });
         }
+        Err(CompileError::new(
+            ErrorCode::InvalidItem,
+            format!("`{from}` isn't a text, a number or a `vanilla_item!`"),
+        ))
     }
 }
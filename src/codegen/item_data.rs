@@ -1,4 +1,7 @@
-use super::misc::VariableScope;
+use super::{
+    misc::{GameValueTarget, VariableScope},
+    template::{ParticleCluster, ParticleData, TemplateItem, TemplateLocation},
+};
 
 /// Represents a literal block of code in a DF code line.
 /// See individual variant documentation for more information.
@@ -49,15 +52,31 @@ pub enum ItemData {
     /// A DF Particle type.
     ///   - String `name` - The name of the particle
     ///   - u32 `amount` - The amount of particles
-    ///   - (i32, i32) `spread` - The spread of particles
-    ///   - (i32, i32, i32) `motion` - The motion of particles
-    ///   - u32 `motion_variation` - The variation of motion
+    ///   - (f32, f32) `spread` - The horizontal and vertical spread of particles
+    ///   - ParticleData `data` - The extra fields of the particle (motion, color, size...)
     Particle {
         name: String,
         amount: u32,
-        spread: (i32, i32),
-        motion: (i32, i32, i32),
-        motion_variation: u32,
+        spread: (f32, f32),
+        data: ParticleData,
+    },
+    /// A DF Game Value.
+    ///   - String `kind` - The name of the game value (e.g. Event Block Location)
+    ///   - GameValueTarget `target` - Who the value is taken from (e.g. Default or Killer)
+    GameValue {
+        kind: String,
+        target: GameValueTarget,
+    },
+    /// A DF Block Tag, which sets an option of the block it's in.
+    ///   - String `tag` - The name of the tag (e.g. Alignment Mode)
+    ///   - String `option` - The selected option (e.g. Centered)
+    ///   - String `action` - The action of the block the tag is in
+    ///   - String `block` - The block the tag is in
+    Tag {
+        tag: String,
+        option: String,
+        action: String,
+        block: String,
     },
     #[default]
     NoData,
@@ -65,55 +84,82 @@ pub enum ItemData {
 
 #[allow(dead_code, unused)]
 impl ItemData {
-    pub fn to_json(&self) -> String {
-        match self {
-            Self::Variable { scope, name } => {
-                format!(
-                    r#""data":{{"name":"{name}","scope":"{}"}}"#,
-                    scope.to_json()
-                )
-            }
-            Self::Number { data } => {
-                format!(r#""data":{{"name":"{data}"}}"#)
-            }
-            Self::Text { data } => {
-                format!(r#""data":{{"name":"{data}"}}"#)
-            }
-            Self::Sound { sound, pitch, vol } => {
-                format!(r#""data":{{"sound":"{sound}","pitch":"{pitch}","vol":"{vol}"}}"#)
-            }
-            Self::VanillaItem { data } => format!(r#""data":{{"item":"{data}"}}"#),
-            Self::Vector { x, y, z } => {
-                format!(r#""data":{{"x":"{x}","y":"{y}","z":"{z}"}}"#)
-            }
+    /// Converts self to the value DF stores in a chest slot.
+    /// Returns None for NoData, which leaves the slot empty.
+    pub fn to_template(&self) -> Option<TemplateItem> {
+        let item = match self {
+            Self::Variable { scope, name } => TemplateItem::Variable {
+                name: name.clone(),
+                scope: scope.clone(),
+            },
+            Self::Number { data } => TemplateItem::Number {
+                name: data.to_string(),
+            },
+            Self::Text { data } => TemplateItem::Text { name: data.clone() },
+            Self::Sound { sound, pitch, vol } => TemplateItem::Sound {
+                sound: sound.clone(),
+                pitch: *pitch,
+                vol: *vol,
+            },
+            Self::VanillaItem { data } => TemplateItem::Item { item: data.clone() },
+            Self::Vector { x, y, z } => TemplateItem::Vector {
+                x: *x,
+                y: *y,
+                z: *z,
+            },
             Self::Location {
                 x,
                 y,
                 z,
                 pitch,
                 yaw,
-            } => {
-                format!(
-                    r#""data":{{"isBlock":false,"loc":{{"x":{x},"y":{y},"z":{z},"pitch":{pitch},"yaw":{yaw}}}}}"#
-                )
-            }
-            Self::Potion { effect, dur, amp } => {
-                format!(r#""data":{{"effect":"{effect}","dur":"{dur}","amp":"{amp}"}}"#)
-            }
+            } => TemplateItem::Location {
+                is_block: false,
+                loc: TemplateLocation {
+                    x: *x,
+                    y: *y,
+                    z: *z,
+                    pitch: *pitch,
+                    yaw: *yaw,
+                },
+            },
+            Self::Potion { effect, dur, amp } => TemplateItem::Potion {
+                pot: effect.clone(),
+                dur: *dur,
+                amp: *amp,
+            },
             Self::Particle {
+                name,
                 amount,
                 spread,
-                motion,
-                motion_variation,
-                name,
-            } => {
-                format!(
-                    r#""data":{{"name":"{name}","amount":"{amount}","spread":"({}, {})", "motion":"({}, {}, {})", "motion_variation":"{motion_variation}"}}"#,
-                    spread.0, spread.1, motion.0, motion.1, motion.2
-                )
-            }
-            Self::NoData => r#""NoData""#.to_string(),
-        }
+                data,
+            } => TemplateItem::Particle {
+                particle: name.clone(),
+                cluster: ParticleCluster {
+                    amount: *amount,
+                    horizontal: spread.0,
+                    vertical: spread.1,
+                },
+                data: data.clone(),
+            },
+            Self::GameValue { kind, target } => TemplateItem::GameValue {
+                kind: kind.clone(),
+                target: target.clone(),
+            },
+            Self::Tag {
+                tag,
+                option,
+                action,
+                block,
+            } => TemplateItem::Tag {
+                option: option.clone(),
+                tag: tag.clone(),
+                action: action.clone(),
+                block: block.clone(),
+            },
+            Self::NoData => return None,
+        };
+        Some(item)
     }
 
     pub fn repr(&self) -> String {
@@ -123,11 +169,31 @@ impl ItemData {
             ItemData::VanillaItem { .. } => "item".to_string(),
             ItemData::Location { .. } => "loc".to_string(),
             ItemData::Vector { .. } => "vec".to_string(),
-            ItemData::Sound { .. } => "sound".to_string(),
+            ItemData::Sound { .. } => "snd".to_string(),
             ItemData::Potion { .. } => "pot".to_string(),
-            ItemData::Particle { .. } => "par".to_string(),
+            ItemData::Particle { .. } => "part".to_string(),
             ItemData::Variable { .. } => "var".to_string(),
+            ItemData::GameValue { .. } => "g_val".to_string(),
+            ItemData::Tag { .. } => "bl_tag".to_string(),
             ItemData::NoData => "nodata".to_string(),
         }
     }
+
+    /// A human readable name of this type of data, for error messages.
+    pub fn describe(&self) -> &'static str {
+        match self {
+            ItemData::Number { .. } => "a Number",
+            ItemData::Text { .. } => "a Text",
+            ItemData::VanillaItem { .. } => "an Item",
+            ItemData::Location { .. } => "a Location",
+            ItemData::Vector { .. } => "a Vector",
+            ItemData::Sound { .. } => "a Sound",
+            ItemData::Potion { .. } => "a Potion",
+            ItemData::Particle { .. } => "a Particle",
+            ItemData::Variable { .. } => "a Variable",
+            ItemData::GameValue { .. } => "a Game Value",
+            ItemData::Tag { .. } => "a Block Tag",
+            ItemData::NoData => "nothing",
+        }
+    }
 }
@@ -1,9 +1,10 @@
 use base64::Engine;
 use serde::{Deserialize, Serialize};
 
-use super::block::Block;
+use super::{block::Block, template::Template};
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
 pub enum VariableScope {
     #[default]
     Local,
@@ -11,65 +12,65 @@ pub enum VariableScope {
     Saved,
 }
 
-impl VariableScope {
-    pub fn to_json(&self) -> String {
-        match self {
-            VariableScope::Local => "local".to_string(),
-            VariableScope::Unsaved => "unsaved".to_string(),
-            VariableScope::Saved => "saved".to_string(),
-        }
-    }
+/// The target of a game value, e.g. the Killer in `killer.Location`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub enum GameValueTarget {
+    #[default]
+    Default,
+    Selection,
+    Killer,
+    Damager,
+    Victim,
+    Shooter,
+    Projectile,
+    LastEntity,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum BracketType {
     Norm,
     Repeat,
 }
 
-impl BracketType {
-    pub fn to_json(&self) -> String {
-        match self {
-            Self::Norm => "norm".to_string(),
-            Self::Repeat => "repeat".to_string(),
-        }
-    }
-}
-
-#[derive(Debug, Clone)]
-#[allow(dead_code, unused)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum BracketDirection {
     Open,
     Close,
 }
 
-#[allow(dead_code, unused)]
-impl BracketDirection {
-    pub fn to_json(&self) -> String {
-        match self {
-            Self::Open => "open".to_string(),
-            Self::Close => "close".to_string(),
-        }
+/// Turns a code line into a template.
+pub fn template(input: &[Block]) -> Template {
+    Template {
+        blocks: input.iter().filter_map(Block::to_template).collect(),
     }
 }
 
-#[allow(dead_code, unused)]
-pub fn process_block_vec(input: Vec<Block>) -> String {
-    let mut out_str = String::new();
-    out_str.push_str(r#"{"blocks":["#);
-    for block in input {
-        out_str.push_str(block.to_json().as_str());
-        out_str.push(',');
+/// The name DF would give the template of a code line, from the definition it starts with.
+pub fn template_name(blocks: &[Block]) -> String {
+    match blocks.first() {
+        Some(Block::EventDefinition { block, action }) if *block == "entity_event" => {
+            format!("Entity Event » {action}")
+        }
+        Some(Block::EventDefinition { action, .. }) => format!("Player Event » {action}"),
+        Some(Block::FunctionDefinition { data, .. }) => format!("Function » {data}"),
+        Some(Block::ProcessDefinition { data, .. }) => format!("Process » {data}"),
+        _ => "Code Line".to_string(),
     }
-    out_str.pop();
-    out_str.push_str(r#"]}"#);
-
-    println!("{}", out_str);
+}
 
-    let mut data_as_bytes = out_str.as_bytes();
-    let mut encoder = libflate::gzip::Encoder::new(Vec::new()).unwrap();
-    std::io::copy(&mut data_as_bytes, &mut encoder).unwrap();
-    let compressed = encoder.finish().into_result().unwrap();
+/// Gzips and then base64 encodes the JSON of a template, which is how a template item holds it.
+/// The gzip header has no timestamp, so building the same code always gives the same data.
+pub fn compress(json: &str) -> std::io::Result<String> {
+    let mut data_as_bytes = json.as_bytes();
+    let header = libflate::gzip::HeaderBuilder::new()
+        .modification_time(0)
+        .finish();
+    let options = libflate::gzip::EncodeOptions::new().header(header);
+    let mut encoder = libflate::gzip::Encoder::with_options(Vec::new(), options)?;
+    std::io::copy(&mut data_as_bytes, &mut encoder)?;
+    let compressed = encoder.finish().into_result()?;
 
-    base64::engine::general_purpose::STANDARD.encode(compressed)
+    Ok(base64::engine::general_purpose::STANDARD.encode(compressed))
 }
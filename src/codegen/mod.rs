@@ -1,4 +1,7 @@
+pub mod action_dump;
 pub mod block;
 pub mod item;
 pub mod item_data;
 pub mod misc;
+pub mod split;
+pub mod template;
@@ -0,0 +1,421 @@
+//! Splits code lines that are too long for a plot into helper functions.
+//!
+//! A code block takes up 2 blocks of a code line, and a bracket 1.
+//! When a line is longer than the plot allows, the code at its end is moved into a function
+//! and replaced by a call to it. Functions share the local variables of whatever calls them,
+//! so the moved code still sees the same locals.
+
+use chumsky::span::SimpleSpan;
+
+use super::{block::Block, misc::BracketDirection};
+use crate::error::{CompileError, ErrorCode};
+
+/// How long a code block is.
+const BLOCK_LENGTH: usize = 2;
+/// How long a bracket is.
+const BRACKET_LENGTH: usize = 1;
+/// How much a condition or repeat adds to what's inside its brackets.
+const GROUP_LENGTH: usize = BLOCK_LENGTH + 2 * BRACKET_LENGTH;
+
+/// A code line as a tree, so brackets are never split apart.
+enum Node<'a> {
+    Block(Block<'a>),
+    /// A block with brackets after it, like an `if`, `else` or `repeat`.
+    /// The brackets are boxed, since a group would be much bigger than a block otherwise.
+    Group {
+        head: Block<'a>,
+        body: Vec<Node<'a>>,
+        brackets: Box<(Block<'a>, Block<'a>)>,
+    },
+}
+
+impl<'a> Node<'a> {
+    fn length(&self) -> usize {
+        match self {
+            Node::Block(_) => BLOCK_LENGTH,
+            Node::Group { body, .. } => GROUP_LENGTH + length(body),
+        }
+    }
+
+    /// Whether this is an `else`, which has to stay right after the condition before it.
+    fn is_else(&self) -> bool {
+        matches!(
+            self,
+            Node::Group {
+                head: Block::Code { block: "else", .. },
+                ..
+            }
+        )
+    }
+
+    fn flatten(self, out: &mut Vec<Block<'a>>) {
+        match self {
+            Node::Block(block) => out.push(block),
+            Node::Group {
+                head,
+                body,
+                brackets,
+            } => {
+                let (open, close) = *brackets;
+                out.push(head);
+                out.push(open);
+                for node in body {
+                    node.flatten(out);
+                }
+                out.push(close);
+            }
+        }
+    }
+}
+
+fn length(nodes: &[Node]) -> usize {
+    nodes.iter().map(Node::length).sum()
+}
+
+fn is_bracket(block: &Block, direction: BracketDirection) -> bool {
+    matches!(block, Block::Bracket { direct, .. } if *direct == direction)
+}
+
+/// Reads blocks into a tree, up to the bracket closing the group they're in.
+fn tree<'a>(blocks: &mut std::iter::Peekable<std::vec::IntoIter<Block<'a>>>) -> Vec<Node<'a>> {
+    let mut nodes = vec![];
+    while let Some(head) = blocks.next_if(|block| !is_bracket(block, BracketDirection::Close)) {
+        match blocks.next_if(|block| is_bracket(block, BracketDirection::Open)) {
+            Some(open) => {
+                let body = tree(blocks);
+                let close = blocks.next().expect("brackets should be balanced");
+                nodes.push(Node::Group {
+                    head,
+                    body,
+                    brackets: Box::new((open, close)),
+                });
+            }
+            None => nodes.push(Node::Block(head)),
+        }
+    }
+    nodes
+}
+
+/// Splits a code line into lines no longer than `limit`, the first being the line itself
+/// and the rest the helper functions it calls.
+/// A line that already fits is left as it is.
+/// Returns an error naming the line if part of it can't be split and is too long on its own.
+pub fn split_line(line: Vec<Block>, limit: usize) -> Result<Vec<Vec<Block>>, CompileError> {
+    let total = line.iter().fold(0, |total, block| {
+        total
+            + match block {
+                Block::Bracket { .. } => BRACKET_LENGTH,
+                _ => BLOCK_LENGTH,
+            }
+    });
+    if total <= limit {
+        return Ok(vec![line]);
+    }
+    let mut blocks = line.into_iter().peekable();
+    let Some(definition) = blocks.next() else {
+        return Ok(vec![]);
+    };
+    let (name, span) = match &definition {
+        Block::EventDefinition { block, action } => (
+            format!(
+                "{}.{action}",
+                if *block == "entity_event" {
+                    "entity"
+                } else {
+                    "player"
+                }
+            ),
+            SimpleSpan::new(0, 0),
+        ),
+        Block::FunctionDefinition { data, span, .. }
+        | Block::ProcessDefinition { data, span, .. } => (data.clone(), *span),
+        _ => ("line".to_string(), SimpleSpan::new(0, 0)),
+    };
+    let mut splitter = Splitter {
+        limit,
+        name,
+        span,
+        helpers: vec![],
+        unfit: None,
+    };
+    let body = splitter.fit(tree(&mut blocks), limit - BLOCK_LENGTH);
+    if let Some(length) = splitter.unfit {
+        return Err(CompileError {
+            code: ErrorCode::Codegen,
+            file: None,
+            span: (span.start < span.end).then_some(span),
+            message: format!(
+                "`{}` is too long for the plot, part of it is {length} blocks long and can't be split, but a code line can only be {limit}",
+                splitter.name
+            ),
+        });
+    }
+
+    let mut first = vec![definition];
+    for node in body {
+        node.flatten(&mut first);
+    }
+    let mut lines = vec![first];
+    lines.append(&mut splitter.helpers);
+    Ok(lines)
+}
+
+/// Moves code that doesn't fit into helper functions.
+///   - usize `limit`: How long a line can be
+///   - String `name`: The name of the line being split, which the helpers are named after
+///   - SimpleSpan `span`: Where the line was defined, for the helpers' blocks
+///   - Vec<Vec<Block>> `helpers`: The helper functions made so far
+///   - Option<usize> `unfit`: How long the first code that couldn't be made to fit is
+struct Splitter<'a> {
+    limit: usize,
+    name: String,
+    span: SimpleSpan,
+    helpers: Vec<Vec<Block<'a>>>,
+    unfit: Option<usize>,
+}
+
+impl<'a> Splitter<'a> {
+    /// Fits nodes into `budget`, moving the ones at the end into a helper function if they don't.
+    /// Conditions and repeats that don't fit are split inside, so what's before them stays in place.
+    fn fit(&mut self, nodes: Vec<Node<'a>>, budget: usize) -> Vec<Node<'a>> {
+        if length(&nodes) <= budget {
+            return nodes;
+        }
+        // Whatever's moved is called at the end, so that call needs room.
+        let room = budget.saturating_sub(BLOCK_LENGTH);
+
+        // An `else` moves along with the condition before it.
+        let mut units: Vec<Vec<Node>> = vec![];
+        for node in nodes {
+            match units.last_mut() {
+                Some(unit) if node.is_else() => unit.push(node),
+                _ => units.push(vec![node]),
+            }
+        }
+
+        let mut kept = vec![];
+        let mut used = 0;
+        let mut units = units.into_iter();
+        while let Some(unit) = units.next() {
+            if used + length(&unit) <= room {
+                used += length(&unit);
+                kept.extend(unit);
+                continue;
+            }
+            // Groups are split inside if each can keep at least a block and a call,
+            // each taking what it needs from the room left but what the ones after it need.
+            let mut rest = units.flatten().collect::<Vec<_>>();
+            let smallest = GROUP_LENGTH + 2 * BLOCK_LENGTH;
+            let groups = unit.iter().all(|node| matches!(node, Node::Group { .. }));
+            if groups && room - used >= smallest * unit.len() {
+                let mut left = room - used;
+                let mut after = unit.len();
+                for node in unit {
+                    after -= 1;
+                    let node = self.shrink(node, left - smallest * after);
+                    left -= node.length();
+                    kept.push(node);
+                }
+            } else if kept.is_empty() {
+                // It would be just as long in a helper, so it's kept rather than moved forever, and reported.
+                self.unfit.get_or_insert(length(&unit));
+                kept.extend(unit);
+            } else {
+                rest.splice(0..0, unit);
+            }
+            if !rest.is_empty() {
+                kept.push(Node::Block(self.helper(rest)));
+            }
+            break;
+        }
+        kept
+    }
+
+    /// Fits what's inside a group's brackets into `budget`, taking the group itself into account.
+    fn shrink(&mut self, node: Node<'a>, budget: usize) -> Node<'a> {
+        match node {
+            Node::Group {
+                head,
+                body,
+                brackets,
+            } => Node::Group {
+                head,
+                body: self.fit(body, budget - GROUP_LENGTH),
+                brackets,
+            },
+            node => node,
+        }
+    }
+
+    /// Moves nodes into a new helper function, returning the call to it.
+    fn helper(&mut self, nodes: Vec<Node<'a>>) -> Block<'a> {
+        let name = format!("{} part {}", self.name, self.helpers.len() + 2);
+        // The helper's place is taken first, so the helpers it makes itself come after it.
+        let index = self.helpers.len();
+        self.helpers.push(vec![]);
+        let body = self.fit(nodes, self.limit - BLOCK_LENGTH);
+
+        let mut line = vec![Block::FunctionDefinition {
+            block: "func",
+            data: name.clone(),
+            docs: vec![],
+            span: self.span,
+        }];
+        for node in body {
+            node.flatten(&mut line);
+        }
+        self.helpers[index] = line;
+        Block::FunctionCall {
+            block: "call_func",
+            data: name,
+            span: self.span,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codegen::misc::BracketType;
+
+    fn action(action: &str) -> Block<'static> {
+        Block::Code {
+            block: "player_action",
+            items: vec![],
+            action: action.to_string(),
+            data: "",
+            target: "Selection",
+            inverted: "",
+            sub_action: String::new(),
+        }
+    }
+
+    fn group(block: &'static str, body: Vec<Block<'static>>) -> Vec<Block<'static>> {
+        let bracket = |direct| Block::Bracket {
+            direct,
+            typ: BracketType::Norm,
+        };
+        let mut head = action("");
+        if let Block::Code { block: name, .. } = &mut head {
+            *name = block;
+        }
+        let mut blocks = vec![head, bracket(BracketDirection::Open)];
+        blocks.extend(body);
+        blocks.push(bracket(BracketDirection::Close));
+        blocks
+    }
+
+    /// A line as a short string per block, e.g. `func greet part 2`, `SendMessage` or `{`.
+    fn describe(line: &[Block]) -> Vec<String> {
+        line.iter()
+            .map(|block| match block {
+                Block::EventDefinition { action, .. } => format!("event {action}"),
+                Block::FunctionDefinition { data, .. } => format!("func {data}"),
+                Block::FunctionCall { data, .. } => format!("call {data}"),
+                Block::Code { block, action, .. } if action.is_empty() => block.to_string(),
+                Block::Code { action, .. } => action.clone(),
+                Block::Bracket { direct, .. } => match direct {
+                    BracketDirection::Open => "{".to_string(),
+                    BracketDirection::Close => "}".to_string(),
+                },
+                _ => unreachable!(),
+            })
+            .collect()
+    }
+
+    fn event(body: Vec<Block<'static>>) -> Vec<Block<'static>> {
+        let mut line = vec![Block::EventDefinition {
+            block: "event",
+            action: "Join".to_string(),
+        }];
+        line.extend(body);
+        line
+    }
+
+    #[test]
+    fn short_lines_are_kept() {
+        let line = event(vec![action("A"), action("B")]);
+        assert_eq!(split_line(line, 6).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn the_end_of_a_line_moves_into_helpers() {
+        let line = event((0..6).map(|i| action(&i.to_string())).collect());
+        let lines = split_line(line, 8)
+            .unwrap()
+            .iter()
+            .map(|line| describe(line))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            lines,
+            vec![
+                vec!["event Join", "0", "1", "call player.Join part 2"],
+                vec![
+                    "func player.Join part 2",
+                    "2",
+                    "3",
+                    "call player.Join part 3"
+                ],
+                vec!["func player.Join part 3", "4", "5"],
+            ]
+        );
+    }
+
+    #[test]
+    fn brackets_stay_balanced() {
+        let mut body = vec![action("A")];
+        body.extend(group(
+            "if_var",
+            (0..6).map(|i| action(&i.to_string())).collect(),
+        ));
+        body.extend(group("else", vec![action("B")]));
+        body.push(action("C"));
+        let lines = split_line(event(body), 20).unwrap();
+        for line in &lines {
+            let length = line.iter().fold(0, |length, block| {
+                length
+                    + if let Block::Bracket { .. } = block {
+                        1
+                    } else {
+                        2
+                    }
+            });
+            assert!(length <= 20, "{:?}", describe(line));
+        }
+        assert_eq!(
+            lines.iter().map(|line| describe(line)).collect::<Vec<_>>(),
+            vec![
+                vec!["event Join", "A", "call player.Join part 2"],
+                vec![
+                    "func player.Join part 2",
+                    "if_var",
+                    "{",
+                    "0",
+                    "call player.Join part 3",
+                    "}",
+                    "else",
+                    "{",
+                    "B",
+                    "}",
+                    "call player.Join part 4"
+                ],
+                vec!["func player.Join part 3", "1", "2", "3", "4", "5"],
+                vec!["func player.Join part 4", "C"],
+            ]
+        );
+    }
+
+    #[test]
+    fn groups_too_long_to_split_are_reported() {
+        let line = event(group(
+            "if_var",
+            (0..6).map(|i| action(&i.to_string())).collect(),
+        ));
+        let error = split_line(line, 8).unwrap_err();
+        assert_eq!(error.code, ErrorCode::Codegen);
+        assert_eq!(
+            error.message,
+            "`player.Join` is too long for the plot, part of it is 16 blocks long and can't be split, but a code line can only be 8"
+        );
+    }
+}
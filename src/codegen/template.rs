@@ -0,0 +1,251 @@
+use serde::{Deserialize, Serialize};
+
+use super::misc::{BracketDirection, BracketType, GameValueTarget, VariableScope};
+
+/// A code template, the JSON DF stores in a template item.
+/// This mirrors DF's schema exactly, so it can be serialized into a template and read back from one.
+///   - Vec<TemplateBlock> `blocks`: The blocks of the code line, in order
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
+pub struct Template {
+    pub blocks: Vec<TemplateBlock>,
+}
+
+/// A single block of a template, tagged by its `id`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "id", rename_all = "lowercase")]
+pub enum TemplateBlock {
+    /// Any code block (events, actions, conditions, functions...)
+    Block(CodeBlock),
+    /// A piston, opening or closing a condition or repeat.
+    ///   - BracketDirection `direct`: Whether the bracket opens or closes
+    ///   - BracketType `typ`: The type of the bracket (norm or repeat)
+    Bracket {
+        direct: BracketDirection,
+        #[serde(rename = "type")]
+        typ: BracketType,
+    },
+}
+
+/// A code block of a template. Fields a block doesn't use are left out of the JSON.
+///   - String `block`: The id of the block, e.g. `player_action`
+///   - Option<TemplateArguments> `args`: The items in the chest of the block (DF leaves this out for `else`)
+///   - Option<String> `action`: The action of the block, e.g. `SendMessage`
+///   - Option<String> `target`: The target of the block, e.g. `Selection`
+///   - Option<String> `inverted`: `NOT` if the condition is inverted
+///   - Option<String> `data`: The name of a function or process
+///   - Option<String> `sub_action`: The condition of a repeat or select block
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
+pub struct CodeBlock {
+    pub block: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub args: Option<TemplateArguments>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub action: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub inverted: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<String>,
+    #[serde(rename = "subAction", default, skip_serializing_if = "Option::is_none")]
+    pub sub_action: Option<String>,
+}
+
+/// The chest of a code block.
+///   - Vec<TemplateSlot> `items`: The filled slots of the chest
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
+pub struct TemplateArguments {
+    pub items: Vec<TemplateSlot>,
+}
+
+/// A filled slot in the chest of a code block.
+///   - TemplateItem `item`: The value in the slot
+///   - i32 `slot`: The index of the slot, from 0 to 26
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TemplateSlot {
+    pub item: TemplateItem,
+    pub slot: i32,
+}
+
+/// A value in a chest, tagged by its `id` with its fields under `data`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "id", content = "data")]
+pub enum TemplateItem {
+    #[serde(rename = "var")]
+    Variable { name: String, scope: VariableScope },
+    /// Numbers are stored as text, since they can contain `%math` expressions.
+    #[serde(rename = "num")]
+    Number { name: String },
+    #[serde(rename = "txt")]
+    Text { name: String },
+    /// A vanilla item, stored as SNBT.
+    #[serde(rename = "item")]
+    Item { item: String },
+    #[serde(rename = "loc")]
+    Location {
+        #[serde(rename = "isBlock")]
+        is_block: bool,
+        loc: TemplateLocation,
+    },
+    #[serde(rename = "vec")]
+    Vector { x: f32, y: f32, z: f32 },
+    #[serde(rename = "snd")]
+    Sound { sound: String, pitch: f32, vol: f32 },
+    #[serde(rename = "pot")]
+    Potion { pot: String, dur: u32, amp: u32 },
+    #[serde(rename = "part")]
+    Particle {
+        particle: String,
+        cluster: ParticleCluster,
+        data: ParticleData,
+    },
+    #[serde(rename = "g_val")]
+    GameValue {
+        #[serde(rename = "type")]
+        kind: String,
+        target: GameValueTarget,
+    },
+    #[serde(rename = "bl_tag")]
+    Tag {
+        option: String,
+        tag: String,
+        action: String,
+        block: String,
+    },
+}
+
+/// The position of a location value.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TemplateLocation {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+    pub pitch: f32,
+    pub yaw: f32,
+}
+
+/// How many particles are spawned, and how far they are spread.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ParticleCluster {
+    pub amount: u32,
+    pub horizontal: f32,
+    pub vertical: f32,
+}
+
+/// The extra fields of a particle. Which ones are used depends on the particle.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ParticleData {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub motion: Option<ParticleMotion>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub motion_variation: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rgb: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color_variation: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size_variation: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub material: Option<String>,
+}
+
+/// The direction particles move in.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ParticleMotion {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use serde_json::Value;
+
+    use super::*;
+
+    /// Turns every number into a float, since `2` and `2.0` are the same value to DF.
+    fn normalize(value: Value) -> Value {
+        match value {
+            Value::Number(n) => Value::from(n.as_f64().expect("number out of range")),
+            Value::Array(values) => Value::Array(values.into_iter().map(normalize).collect()),
+            Value::Object(map) => {
+                Value::Object(map.into_iter().map(|(k, v)| (k, normalize(v))).collect())
+            }
+            other => other,
+        }
+    }
+
+    fn sample_files(dir: &Path, out: &mut Vec<std::path::PathBuf>) {
+        for entry in std::fs::read_dir(dir).expect("failed to read samples") {
+            let path = entry.expect("failed to read samples").path();
+            if path.is_dir() {
+                sample_files(&path, out);
+            } else if path.extension().is_some_and(|ext| ext == "json") {
+                out.push(path);
+            }
+        }
+    }
+
+    #[test]
+    fn samples_round_trip() {
+        let mut files = vec![];
+        sample_files(
+            &Path::new(env!("CARGO_MANIFEST_DIR")).join("backend/samples"),
+            &mut files,
+        );
+        assert!(!files.is_empty());
+        for file in files {
+            let raw = std::fs::read_to_string(&file).expect("failed to read sample");
+            let template: Template = serde_json::from_str(&raw)
+                .unwrap_or_else(|e| panic!("failed to parse {}: {e}", file.display()));
+            let json = serde_json::to_string(&template).expect("failed to serialize");
+            let original: Value = serde_json::from_str(&raw).unwrap();
+            let emitted: Value = serde_json::from_str(&json).unwrap();
+            assert_eq!(
+                normalize(original),
+                normalize(emitted),
+                "{} changed in a round trip",
+                file.display()
+            );
+        }
+    }
+
+    #[test]
+    fn no_data_leaves_slot_empty() {
+        use crate::codegen::{block::Block, item::Item, item_data::ItemData};
+
+        let block = Block::Code {
+            block: "player_action",
+            items: vec![Item {
+                id: "nodata".to_string(),
+                slot: 0,
+                item: ItemData::NoData,
+            }],
+            action: "SendMessage".to_string(),
+            data: "",
+            target: "",
+            inverted: "",
+            sub_action: String::new(),
+        };
+        let template: TemplateBlock = serde_json::from_str(&block.to_json()).unwrap();
+        let TemplateBlock::Block(code) = template else {
+            panic!("expected a code block");
+        };
+        assert_eq!(code.args, Some(TemplateArguments::default()));
+    }
+
+    #[test]
+    fn text_is_escaped() {
+        let item = TemplateItem::Text {
+            name: r#"say "hi" \o/"#.to_string(),
+        };
+        let json = serde_json::to_string(&item).unwrap();
+        assert_eq!(json, r#"{"id":"txt","data":{"name":"say \"hi\" \\o/"}}"#);
+        assert_eq!(serde_json::from_str::<TemplateItem>(&json).unwrap(), item);
+    }
+}
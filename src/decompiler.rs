@@ -0,0 +1,675 @@
+use std::io::Read;
+
+use base64::Engine;
+
+use crate::{
+    codegen::{
+        action_dump::ACTION_DUMP,
+        misc::{BracketDirection, GameValueTarget, VariableScope},
+        template::{CodeBlock, Template, TemplateBlock, TemplateItem, TemplateSlot},
+    },
+    parser::parse::START_PROCESS_DEFAULTS,
+};
+
+/// A template read from its compressed form, along with its name if it came in a template item envelope.
+pub struct DecodedTemplate {
+    pub name: Option<String>,
+    pub template: Template,
+}
+
+/// Decodes the data of a template item. This is the inverse of `output::BuiltLine::code`.
+/// Accepts the base64+gzip code on its own, the `{"author","name","version","code"}` envelope around it,
+/// or the plain `{"blocks":[...]}` JSON.
+pub fn decode_template(input: &str) -> Result<DecodedTemplate, String> {
+    let input = input.trim().trim_matches('\'');
+    if input.starts_with('{') {
+        let value: serde_json::Value =
+            serde_json::from_str(input).map_err(|e| format!("invalid template JSON: {e}"))?;
+        if let Some(code) = value.get("code").and_then(|code| code.as_str()) {
+            let name = value
+                .get("name")
+                .and_then(|name| name.as_str())
+                .map(strip_formatting);
+            return Ok(DecodedTemplate {
+                name,
+                template: decode_template(code)?.template,
+            });
+        }
+        let template =
+            serde_json::from_value(value).map_err(|e| format!("unsupported template JSON: {e}"))?;
+        return Ok(DecodedTemplate {
+            name: None,
+            template,
+        });
+    }
+
+    let compressed = base64::engine::general_purpose::STANDARD
+        .decode(input)
+        .map_err(|e| format!("template data is not valid base64: {e}"))?;
+    let mut json = String::new();
+    libflate::gzip::Decoder::new(compressed.as_slice())
+        .and_then(|mut decoder| decoder.read_to_string(&mut json))
+        .map_err(|e| format!("template data is not valid gzip: {e}"))?;
+    let template =
+        serde_json::from_str(&json).map_err(|e| format!("unsupported template JSON: {e}"))?;
+    Ok(DecodedTemplate {
+        name: None,
+        template,
+    })
+}
+
+/// Turns a template back into Blackstone source.
+/// Blocks the language can't express yet are kept as `// raw:` comments holding their JSON.
+pub fn decompile(template: &Template) -> String {
+    let mut writer = Writer::default();
+    let blocks = &template.blocks;
+    let mut index = 0;
+
+    let definition = match blocks.first() {
+        Some(TemplateBlock::Block(code)) => definition_header(code),
+        _ => None,
+    };
+    match definition {
+        Some(header) => {
+            writer.line(&header);
+            writer.indent += 1;
+            index += 1;
+            writer.body(blocks, &mut index);
+            writer.indent -= 1;
+            writer.line("}");
+        }
+        None => {
+            writer.line("// This template doesn't start with an event, function or process.");
+            writer.body(blocks, &mut index);
+        }
+    }
+    while index < blocks.len() {
+        // Only stray closing brackets are left over here.
+        writer.raw(&blocks[index]);
+        index += 1;
+    }
+    writer.out
+}
+
+/// Builds up the source, keeping track of the indentation.
+#[derive(Default)]
+struct Writer {
+    out: String,
+    indent: usize,
+}
+
+impl Writer {
+    fn line(&mut self, line: &str) {
+        for _ in 0..self.indent {
+            self.out.push_str("    ");
+        }
+        self.out.push_str(line);
+        self.out.push('\n');
+    }
+
+    fn raw(&mut self, block: &TemplateBlock) {
+        let json = serde_json::to_string(block).expect("failed to serialize block");
+        self.line(&format!("// raw: {json}"));
+    }
+
+    /// Writes blocks until the end of the template or a closing bracket, which is left for the caller.
+    fn body(&mut self, blocks: &[TemplateBlock], index: &mut usize) {
+        while let Some(block) = blocks.get(*index) {
+            let code = match block {
+                TemplateBlock::Bracket {
+                    direct: BracketDirection::Close,
+                    ..
+                } => return,
+                TemplateBlock::Bracket { .. } => {
+                    self.raw(block);
+                    *index += 1;
+                    continue;
+                }
+                TemplateBlock::Block(code) => code,
+            };
+            *index += 1;
+
+            if let Some(params) = function_parameters(code) {
+                if let Some(TemplateBlock::Block(call)) = blocks.get(*index) {
+                    if let Some(line) = call_statement(call, &params) {
+                        self.line(&format!("{line};"));
+                        *index += 1;
+                        continue;
+                    }
+                }
+            }
+
+            let opens = matches!(
+                blocks.get(*index),
+                Some(TemplateBlock::Bracket {
+                    direct: BracketDirection::Open,
+                    ..
+                })
+            );
+            if !opens {
+                match statement(code) {
+                    Some(line) => {
+                        if let Some(target) = unwritten_target(code) {
+                            self.line(&format!("// target: {target}"));
+                        }
+                        self.line(&format!("{line};"));
+                    }
+                    None => self.raw(block),
+                }
+                continue;
+            }
+
+            let open = &blocks[*index];
+            *index += 1;
+            let header = header(code);
+            match &header {
+                Some(header) => self.line(&format!("{header} {{")),
+                None => {
+                    self.raw(block);
+                    self.raw(open);
+                }
+            }
+            self.indent += 1;
+            self.body(blocks, index);
+            self.indent -= 1;
+            let close = blocks.get(*index);
+            *index += 1;
+            match (&header, close) {
+                (Some(_), _) => self.line("};"),
+                (None, Some(close)) => self.raw(close),
+                (None, None) => {}
+            }
+        }
+    }
+}
+
+/// The first line of an event, function or process definition.
+fn definition_header(code: &CodeBlock) -> Option<String> {
+    match code.block.as_str() {
+        "event" => Some(format!("event player.{} {{", ident(code.action.as_ref()?)?)),
+        "entity_event" => Some(format!("event entity.{} {{", ident(code.action.as_ref()?)?)),
+        "func" => Some(format!("func {}() {{", function_name(code.data.as_ref()?))),
+        "process" => Some(format!("proc {}() {{", function_name(code.data.as_ref()?))),
+        _ => None,
+    }
+}
+
+/// A block that is followed by brackets, e.g. `if player.IsSneaking`.
+fn header(code: &CodeBlock) -> Option<String> {
+    let action = code.action.as_deref().unwrap_or_default();
+    let not = if code.inverted.as_deref() == Some("NOT") {
+        "!"
+    } else {
+        ""
+    };
+    let prefix = match code.block.as_str() {
+        "if_player" => "player",
+        "if_entity" => "entity",
+        "if_game" => "plot",
+        "if_var" => {
+            let (var, args) = split_variable(code)?;
+            return Some(format!(
+                "if {not}var {var} {}",
+                with_arguments(code, action, &args)?
+            ));
+        }
+        "repeat" => {
+            let (args, tags) = arguments(code)?;
+            let mut header = format!("repeat {}", ident(action)?);
+            if let Some(sub_action) = code.sub_action.as_deref().filter(|s| !s.is_empty()) {
+                header.push_str(&format!("::{}", ident(sub_action)?));
+            }
+            if !args.is_empty() || !tags.is_empty() {
+                header.push_str(&argument_list(&args, &tags));
+            }
+            return Some(header);
+        }
+        "else" => return Some("else".to_string()),
+        _ => return None,
+    };
+    let (args, tags) = arguments(code)?;
+    if !args.is_empty() || !tags.is_empty() {
+        return None;
+    }
+    Some(format!("if {not}{prefix}.{}", ident(action)?))
+}
+
+/// A block that stands on its own, e.g. `player.SendMessage("hi")`.
+fn statement(code: &CodeBlock) -> Option<String> {
+    let action = code.action.as_deref().unwrap_or_default();
+    let prefix = match code.block.as_str() {
+        "player_action" => "player",
+        "entity_action" => "entity",
+        "game_action" => "plot",
+        "set_var" => {
+            let (var, args) = split_variable(code)?;
+            return Some(format!(
+                "var {var} {}",
+                with_arguments(code, action, &args)?
+            ));
+        }
+        "call_func" | "start_process" => return call_statement(code, &[]),
+        _ => return None,
+    };
+    let (args, tags) = arguments(code)?;
+    Some(format!(
+        "{prefix}.{}{}",
+        ident(action)?,
+        argument_list(&args, &tags)
+    ))
+}
+
+/// The target of an action, if it's one that can't be written yet.
+fn unwritten_target(code: &CodeBlock) -> Option<&str> {
+    code.target
+        .as_deref()
+        .filter(|target| !target.is_empty() && *target != "Selection")
+}
+
+/// `call` or `start`, passing the given parameters.
+fn call_statement(code: &CodeBlock, params: &[String]) -> Option<String> {
+    let name = function_name(code.data.as_ref()?);
+    match code.block.as_str() {
+        "call_func" => Some(format!("call {name}({})", params.join(", "))),
+        "start_process" => {
+            let mut tags = vec![];
+            for slot in items(code) {
+                let TemplateItem::Tag { tag, option, .. } = &slot.item else {
+                    return None;
+                };
+                let (_, default) = START_PROCESS_DEFAULTS
+                    .iter()
+                    .find(|(name, _)| name == tag)?;
+                if option != default {
+                    tags.push(format!("{} = {}", tag_name(tag), text(option)));
+                }
+            }
+            Some(format!("start {name}{}", argument_list(params, &tags)))
+        }
+        _ => None,
+    }
+}
+
+/// The parameters passed to a function, if this block builds the `__FUNCTION_PARAMETERS` list.
+fn function_parameters(code: &CodeBlock) -> Option<Vec<String>> {
+    if code.block != "set_var" || code.action.as_deref() != Some("CreateList") {
+        return None;
+    }
+    let list = items(code).find(|slot| slot.slot == 0)?;
+    if !matches!(&list.item, TemplateItem::Variable { name, scope: VariableScope::Local } if name == "__FUNCTION_PARAMETERS")
+    {
+        return None;
+    }
+    values(code, 1)
+}
+
+/// `= with(...)` for plain operators, or `= Action(...)` for named actions.
+fn with_arguments(code: &CodeBlock, action: &str, args: &[String]) -> Option<String> {
+    let (_, tags) = arguments(code)?;
+    let list = argument_list(args, &tags);
+    Some(match action {
+        "=" | "+" | "-" | "/" | "%" => format!("{action} with{list}"),
+        "x" => format!("* with{list}"),
+        _ => format!("= {}{list}", ident(action)?),
+    })
+}
+
+/// Splits off the variable in the first slot of a set_var or if_var block.
+fn split_variable(code: &CodeBlock) -> Option<(String, Vec<String>)> {
+    let (mut args, _) = arguments(code)?;
+    let first = items(code).find(|slot| slot.slot == 0)?;
+    if !matches!(first.item, TemplateItem::Variable { .. }) || args.is_empty() {
+        return None;
+    }
+    let var = args.remove(0);
+    Some((var, args))
+}
+
+fn items(code: &CodeBlock) -> impl Iterator<Item = &TemplateSlot> {
+    code.args.iter().flat_map(|args| args.items.iter())
+}
+
+/// The arguments of a block, and the tags that aren't set to their default option.
+/// Returns None if any of them can't be written.
+fn arguments(code: &CodeBlock) -> Option<(Vec<String>, Vec<String>)> {
+    let args = values(code, 0)?;
+    let mut tags = vec![];
+    for slot in items(code) {
+        let TemplateItem::Tag {
+            tag, option, block, ..
+        } = &slot.item
+        else {
+            continue;
+        };
+        let action = ACTION_DUMP.find_action(block, code.action.as_deref()?)?;
+        let found = action.find_tag(tag)?;
+        if found.default_option != *option {
+            tags.push(format!("{} = {}", tag_name(&found.name), text(option)));
+        }
+    }
+    Some((args, tags))
+}
+
+/// The values in the chest of a block from the given slot onwards, or None if any of them can't be written.
+fn values(code: &CodeBlock, first: usize) -> Option<Vec<String>> {
+    let mut slots = items(code)
+        .filter(|slot| !matches!(slot.item, TemplateItem::Tag { .. }))
+        .filter(|slot| usize::try_from(slot.slot).is_ok_and(|slot| slot >= first))
+        .collect::<Vec<_>>();
+    slots.sort_by_key(|slot| slot.slot);
+    let mut values = vec![];
+    for (index, slot) in slots.into_iter().enumerate() {
+        // Arguments are always compiled into consecutive slots, so gaps can't be written.
+        if usize::try_from(slot.slot).ok()? != first + index {
+            return None;
+        }
+        values.push(argument(&slot.item)?);
+    }
+    Some(values)
+}
+
+fn argument_list(args: &[String], tags: &[String]) -> String {
+    format!("({})", argument_list_inner(&args.join(", "), tags))
+}
+
+/// Values followed by the named ones after a `;`, as in argument lists and particles.
+fn argument_list_inner(values: &str, named: &[String]) -> String {
+    if named.is_empty() {
+        values.to_string()
+    } else {
+        format!("{values}; {}", named.join(", "))
+    }
+}
+
+/// A single argument, or None if it has no syntax yet.
+fn argument(item: &TemplateItem) -> Option<String> {
+    match item {
+        TemplateItem::Variable { name, scope } => {
+            if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '%') {
+                return None;
+            }
+            let scope = match scope {
+                VariableScope::Local => "local",
+                VariableScope::Saved => "save",
+                VariableScope::Unsaved => "game",
+            };
+            Some(format!("{scope}.{name}"))
+        }
+        TemplateItem::Number { name } => {
+            let unsigned = name.strip_prefix('-').unwrap_or(name);
+            let (whole, fraction) = unsigned.split_once('.').unwrap_or((unsigned, "0"));
+            let digits = |s: &str| !s.is_empty() && s.chars().all(|c| c.is_ascii_digit());
+            (digits(whole) && digits(fraction)).then(|| name.clone())
+        }
+        TemplateItem::Text { name } => Some(text(name)),
+        TemplateItem::Item { item } => {
+            let rest = item.strip_prefix("{Count:")?;
+            let (count, rest) = rest.split_once("b,DF_NBT:")?;
+            let (_, rest) = rest.split_once(",id:\"minecraft:")?;
+            let id = rest.strip_suffix("\"}")?;
+            if !id.chars().all(|c| c.is_ascii_lowercase() || c == '_') {
+                return None;
+            }
+            if count == "1" {
+                Some(format!("item(\"{id}\")"))
+            } else if !id.contains('_') && count.chars().all(|c| c.is_ascii_digit()) {
+                Some(format!("items({id}; {count})"))
+            } else {
+                None
+            }
+        }
+        TemplateItem::GameValue { kind, target } => {
+            let target = match target {
+                GameValueTarget::Default => "value",
+                GameValueTarget::Selection => "selection",
+                GameValueTarget::Killer => "killer",
+                GameValueTarget::Damager => "damager",
+                GameValueTarget::Victim => "victim",
+                GameValueTarget::Shooter => "shooter",
+                GameValueTarget::Projectile => "projectile",
+                GameValueTarget::LastEntity => "lastEntity",
+            };
+            let name = kind
+                .chars()
+                .filter(char::is_ascii_alphanumeric)
+                .collect::<String>();
+            ACTION_DUMP.find_game_value(&name)?;
+            Some(format!("{target}.{name}"))
+        }
+        TemplateItem::Sound { sound, pitch, vol } => {
+            Some(format!("sound({}, {pitch}, {vol})", text(sound)))
+        }
+        TemplateItem::Vector { x, y, z } => Some(format!("vec({x}, {y}, {z})")),
+        TemplateItem::Potion { pot, dur, amp } => {
+            Some(format!("potion({}, {dur}, {amp})", text(pot)))
+        }
+        TemplateItem::Particle {
+            particle,
+            cluster,
+            data,
+        } => {
+            let mut fields = vec![];
+            if let Some(motion) = &data.motion {
+                fields.push(format!(
+                    "motion = vec({}, {}, {})",
+                    motion.x, motion.y, motion.z
+                ));
+            }
+            if let Some(variation) = data.motion_variation {
+                fields.push(format!("motionVariation = {variation}"));
+            }
+            if let Some(rgb) = data.rgb {
+                fields.push(format!("color = \"#{rgb:06X}\""));
+            }
+            if let Some(variation) = data.color_variation {
+                fields.push(format!("colorVariation = {variation}"));
+            }
+            if let Some(size) = data.size {
+                fields.push(format!("size = {size}"));
+            }
+            if let Some(variation) = data.size_variation {
+                fields.push(format!("sizeVariation = {variation}"));
+            }
+            if let Some(material) = &data.material {
+                fields.push(format!("material = {}", text(material)));
+            }
+            let values = format!(
+                "{}, {}, {}, {}",
+                text(particle),
+                cluster.amount,
+                cluster.horizontal,
+                cluster.vertical
+            );
+            Some(format!(
+                "particle({})",
+                argument_list_inner(&values, &fields)
+            ))
+        }
+        _ => None,
+    }
+}
+
+/// A text literal, escaping anything the parser would read differently.
+/// `%var()` placeholders are kept as they are, since they compile back to themselves.
+fn text(text: &str) -> String {
+    let mut literal = String::from('"');
+    for c in text.chars() {
+        match c {
+            '"' | '\\' | '{' | '}' => {
+                literal.push('\\');
+                literal.push(c);
+            }
+            '\n' => literal.push_str("\\n"),
+            '\t' => literal.push_str("\\t"),
+            c => literal.push(c),
+        }
+    }
+    literal.push('"');
+    literal
+}
+
+/// The name of an action as an identifier, or None if it isn't one.
+fn ident(name: &str) -> Option<&str> {
+    let mut chars = name.chars();
+    let first = chars.next()?;
+    let valid = (first.is_ascii_alphabetic() || first == '%')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '%');
+    valid.then_some(name)
+}
+
+/// A function or process name as an identifier. Characters that can't be written are dropped.
+fn function_name(name: &str) -> String {
+    let name = name
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == '%')
+        .skip_while(|c| c.is_ascii_digit())
+        .collect::<String>();
+    if name.is_empty() {
+        "unnamed".to_string()
+    } else {
+        name
+    }
+}
+
+/// The name of a tag as it's written in an argument list, e.g. `alignmentMode` for `Alignment Mode`.
+fn tag_name(name: &str) -> String {
+    let mut out = String::new();
+    for (index, word) in name
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .enumerate()
+    {
+        let mut chars = word.chars();
+        if let Some(first) = chars.next() {
+            if index == 0 {
+                out.extend(first.to_lowercase());
+            } else {
+                out.extend(first.to_uppercase());
+            }
+            out.push_str(chars.as_str());
+        }
+    }
+    out
+}
+
+/// Removes Minecraft `§` formatting codes from a template name.
+fn strip_formatting(name: &str) -> String {
+    let mut out = String::new();
+    let mut chars = name.chars();
+    while let Some(c) = chars.next() {
+        if c == '§' {
+            chars.next();
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use chumsky::Parser;
+
+    use super::*;
+
+    /// Compiles the source of a single definition and decompiles it again.
+    fn round_trip(source: &str) -> String {
+        let lines = crate::parser::parse::parser()
+            .parse(source)
+            .into_result()
+            .expect("failed to parse");
+        let blocks = lines.into_iter().next().expect("no definitions");
+        let template = Template {
+            blocks: blocks
+                .iter()
+                .flatten()
+                .filter_map(|b| b.to_template())
+                .collect(),
+        };
+        decompile(&template)
+    }
+
+    #[test]
+    fn decompiles_compiled_source() {
+        let source = r##"event player.Join {
+    player.SendMessage("Hello", local.name; alignmentMode = "Centered");
+    player.PlaySound(sound("Creeper Death", 1.5, 2));
+    player.GivePotion(potion("Speed", 200, 1));
+    player.SetVelocity(vec(0, -0.5, 1));
+    player.Particle(particle("Dust", 5, 0.5, 0; color = "#00FF00", colorVariation = 0, size = 2, sizeVariation = 0));
+    var local.count + with(1);
+    if !var local.count = with(10) {
+        call greet(local.name);
+    };
+    else {
+        repeat Multiple(3) {
+            plot.CancelEvent();
+        };
+    };
+}
+"##;
+        assert_eq!(round_trip(source), source);
+    }
+
+    #[test]
+    fn start_tags() {
+        let source = r#"event player.Join {
+    start loop();
+    start loop(local.name; localVariables = "Copy");
+}
+"#;
+        assert_eq!(round_trip(source), source);
+    }
+
+    #[test]
+    fn negative_numbers() {
+        let source = r#"event player.Join {
+    var local.count + with(-1, -0.5);
+}
+"#;
+        assert_eq!(round_trip(source), source);
+    }
+
+    #[test]
+    fn escapes_text() {
+        let source = r#"event player.Join {
+    player.SendMessage("say \"hi\" \{\}\n\\", "Hi %var(name)");
+}
+"#;
+        assert_eq!(round_trip(source), source);
+    }
+
+    #[test]
+    fn unwritable_blocks_fall_back_to_raw() {
+        let template = Template {
+            blocks: vec![TemplateBlock::Block(CodeBlock {
+                block: "control".to_string(),
+                action: Some("End".to_string()),
+                ..Default::default()
+            })],
+        };
+        assert_eq!(
+            decompile(&template),
+            "// This template doesn't start with an event, function or process.\n\
+             // raw: {\"id\":\"block\",\"block\":\"control\",\"action\":\"End\"}\n"
+        );
+    }
+
+    #[test]
+    fn decodes_compressed_templates() {
+        let code = crate::output::BuiltLine::new(
+            "main",
+            1,
+            &[crate::codegen::block::Block::EventDefinition {
+                block: "event",
+                action: "Join".to_string(),
+            }],
+        )
+        .code()
+        .expect("failed to encode");
+        let envelope = format!(r#"{{"author":"me","name":"§aJoin","version":1,"code":"{code}"}}"#);
+        let decoded = decode_template(&envelope).expect("failed to decode");
+        assert_eq!(decoded.name.as_deref(), Some("Join"));
+        assert_eq!(decompile(&decoded.template), "event player.Join {\n}\n");
+    }
+}
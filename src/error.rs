@@ -0,0 +1,116 @@
+//! The errors found while building a project, each with a code so it can be looked up.
+
+use std::fmt;
+
+use chumsky::{error::RichReason, prelude::Rich, span::SimpleSpan};
+
+/// What kind of error a `CompileError` is, shown as its code, e.g. `[E0201]`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCode {
+    /// The script doesn't follow Blackstone's syntax.
+    Syntax,
+    /// The script parsed, but doesn't fit DF, e.g. an unknown action or an argument of the wrong type.
+    Invalid,
+    /// A value that can't be turned into an item.
+    InvalidItem,
+    /// A script that's `use`d can't be found or read.
+    MissingScript,
+    /// Scripts `use` each other in a cycle.
+    ImportCycle,
+    /// A function or process is defined more than once.
+    DuplicateName,
+    /// A call that doesn't resolve to exactly one definition.
+    UnresolvedCall,
+    /// An `assert` outside of a `test`.
+    MisplacedAssert,
+    /// A code line couldn't be turned into a template.
+    Codegen,
+    /// A template couldn't be sent to its target.
+    Send,
+    /// A test failed.
+    TestFailure,
+}
+
+impl ErrorCode {
+    /// The code of this kind of error.
+    pub fn code(self) -> &'static str {
+        match self {
+            ErrorCode::Syntax => "E0001",
+            ErrorCode::Invalid => "E0002",
+            ErrorCode::InvalidItem => "E0003",
+            ErrorCode::MissingScript => "E0101",
+            ErrorCode::ImportCycle => "E0102",
+            ErrorCode::DuplicateName => "E0201",
+            ErrorCode::UnresolvedCall => "E0202",
+            ErrorCode::MisplacedAssert => "E0203",
+            ErrorCode::Codegen => "E0301",
+            ErrorCode::Send => "E0401",
+            ErrorCode::TestFailure => "E0501",
+        }
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.code())
+    }
+}
+
+/// An error found while building a project, or a failing test.
+///   - ErrorCode `code`: What kind of error it is
+///   - Option<usize> `file`: The script the error is in, as an index into `Project::files`, if it's in one
+///   - Option<SimpleSpan> `span`: Where in the script the error is, if that's known
+///   - String `message`: What went wrong
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompileError {
+    pub code: ErrorCode,
+    pub file: Option<usize>,
+    pub span: Option<SimpleSpan>,
+    pub message: String,
+}
+
+impl CompileError {
+    /// An error that isn't in a script, e.g. failing to connect to `recode`.
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> CompileError {
+        CompileError {
+            code,
+            file: None,
+            span: None,
+            message: message.into(),
+        }
+    }
+
+    /// An error at a place in a script.
+    pub fn at(
+        code: ErrorCode,
+        file: usize,
+        span: SimpleSpan,
+        message: impl Into<String>,
+    ) -> CompileError {
+        CompileError {
+            code,
+            file: Some(file),
+            span: Some(span),
+            message: message.into(),
+        }
+    }
+
+    /// An error of the parser. Custom errors are the checks made while parsing, like unknown actions.
+    pub fn from_parser(file: usize, error: &Rich<char>) -> CompileError {
+        let code = match error.reason() {
+            RichReason::Custom(_) => ErrorCode::Invalid,
+            _ => ErrorCode::Syntax,
+        };
+        let span = *error.span();
+        let message = match error.reason() {
+            // Some parsers fail without saying what they wanted, which chumsky words as
+            // "found end of input expected something else" even when it isn't the end.
+            RichReason::ExpectedFound {
+                expected,
+                found: None,
+            } if expected.is_empty() && span.start < span.end => "Unexpected code here".to_string(),
+            reason => reason.to_string(),
+        };
+        CompileError::at(code, file, span, message)
+    }
+}
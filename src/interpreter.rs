@@ -0,0 +1,845 @@
+//! An offline interpreter for compiled code lines, which `build-test` uses to run the `test` blocks of a project.
+//! It simulates variables, Set Variable arithmetic, If Variable conditions, repeats and calls,
+//! along with a mock player that records the messages it's sent.
+//! Anything that needs a server (If Player conditions, game values...) fails the test instead.
+
+use std::{collections::HashMap, fmt};
+
+use chumsky::span::SimpleSpan;
+
+use crate::{
+    codegen::{
+        action_dump::ACTION_DUMP,
+        block::{Assertion, Block},
+        item::Item,
+        item_data::ItemData,
+        misc::{BracketDirection, VariableScope},
+    },
+    error::{CompileError, ErrorCode},
+    modules::{Module, Project},
+};
+
+/// How many blocks a test can run, so an infinite loop fails the test instead of hanging.
+const MAX_STEPS: usize = 100_000;
+
+/// The value of a variable.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Number(f64),
+    Text(String),
+    List(Vec<Value>),
+    /// Any other item (e.g. a Location), which can only be stored and compared.
+    Item(ItemData),
+}
+
+impl Value {
+    /// Whether two values are equal, allowing for rounding errors between numbers.
+    fn equals(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Value::Number(a), Value::Number(b)) => (a - b).abs() < 1e-9,
+            (Value::List(a), Value::List(b)) => {
+                a.len() == b.len() && a.iter().zip(b).all(|(a, b)| a.equals(b))
+            }
+            _ => self == other,
+        }
+    }
+
+    /// The value as it's written in a failure message, with texts in quotes.
+    fn quoted(&self) -> String {
+        match self {
+            Value::Text(text) => format!("{text:?}"),
+            other => other.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            // DF shows numbers with up to 3 decimals.
+            Value::Number(number) => {
+                let shown = format!("{number:.3}");
+                let shown = shown.trim_end_matches('0').trim_end_matches('.');
+                write!(f, "{}", if shown == "-0" { "0" } else { shown })
+            }
+            Value::Text(text) => write!(f, "{text}"),
+            Value::List(values) => write!(
+                f,
+                "[{}]",
+                values
+                    .iter()
+                    .map(Value::quoted)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            Value::Item(data) => write!(f, "{}", data.describe()),
+        }
+    }
+}
+
+/// The result of a test.
+///   - usize `file`: The script the test is in, as an index into `Project::files`
+///   - String `name`: The name of the test
+///   - Result<(), CompileError> `outcome`: Why the test failed, if it did
+#[derive(Debug, Clone)]
+pub struct TestResult {
+    pub file: usize,
+    pub name: String,
+    pub outcome: Result<(), CompileError>,
+}
+
+/// Runs every test in the project's own scripts, each with fresh variables and a fresh player.
+/// Tests can call the functions and processes of every module, including the ones of libraries.
+pub fn run_tests(project: &Project, modules: &[Module]) -> Vec<TestResult> {
+    let mut functions = HashMap::new();
+    let mut processes = HashMap::new();
+    for line in modules.iter().flat_map(|module| &module.lines) {
+        let blocks = line.iter().flatten().collect::<Vec<_>>();
+        match blocks.first() {
+            Some(Block::FunctionDefinition { data, .. }) => {
+                functions.insert(data.as_str(), blocks[1..].to_vec());
+            }
+            Some(Block::ProcessDefinition { data, .. }) => {
+                processes.insert(data.as_str(), blocks[1..].to_vec());
+            }
+            _ => {}
+        }
+    }
+
+    let mut results = vec![];
+    for module in modules {
+        if project.files[module.file].package.is_some() {
+            continue;
+        }
+        for line in &module.tests {
+            let blocks = line.iter().flatten().collect::<Vec<_>>();
+            let Some(Block::TestDefinition { name, span }) = blocks.first() else {
+                continue;
+            };
+            let mut interpreter = Interpreter {
+                functions: &functions,
+                processes: &processes,
+                game: Variables::new(),
+                saved: Variables::new(),
+                messages: vec![],
+                steps: 0,
+                depth: 0,
+            };
+            let outcome = interpreter
+                .run(&blocks[1..], &mut Variables::new())
+                .map_err(|failure| {
+                    CompileError::at(
+                        ErrorCode::TestFailure,
+                        module.file,
+                        failure.span.unwrap_or(*span),
+                        failure.message,
+                    )
+                });
+            results.push(TestResult {
+                file: module.file,
+                name: name.clone(),
+                outcome,
+            });
+        }
+    }
+    results
+}
+
+/// Variables of one scope, by name.
+type Variables = HashMap<String, Value>;
+
+/// The blocks of each function or process after its definition, by name.
+type Definitions<'m, 'a> = HashMap<&'m str, Vec<&'m Block<'a>>>;
+
+/// Why a test failed.
+///   - Option<SimpleSpan> `span`: Where in the test it failed, or None if that isn't known yet
+///   - String `message`: What went wrong
+struct Failure {
+    span: Option<SimpleSpan>,
+    message: String,
+}
+
+impl Failure {
+    fn new(message: impl Into<String>) -> Failure {
+        Failure {
+            span: None,
+            message: message.into(),
+        }
+    }
+}
+
+/// The state of a running test.
+///   - Variables `game`: The game (unsaved) variables
+///   - Variables `saved`: The saved variables
+///   - Vec<String> `messages`: Every message sent to the player so far
+///   - usize `steps`: How many blocks have run
+///   - usize `depth`: How many calls deep the running code is
+struct Interpreter<'d, 'm, 'a> {
+    functions: &'d Definitions<'m, 'a>,
+    processes: &'d Definitions<'m, 'a>,
+    game: Variables,
+    saved: Variables,
+    messages: Vec<String>,
+    steps: usize,
+    depth: usize,
+}
+
+impl<'a> Interpreter<'_, '_, 'a> {
+    /// Runs blocks, with the given local variables.
+    fn run(&mut self, blocks: &[&Block<'a>], locals: &mut Variables) -> Result<(), Failure> {
+        let mut index = 0;
+        // Whether the condition right before this block passed, for an `else`.
+        let mut last_condition = None;
+        while let Some(&block) = blocks.get(index) {
+            index += 1;
+            self.steps += 1;
+            if self.steps > MAX_STEPS {
+                return Err(Failure::new(format!(
+                    "The test ran more than {MAX_STEPS} blocks, is there an infinite loop?"
+                )));
+            }
+            match block {
+                Block::Code {
+                    block: "if_var" | "if_player" | "if_entity" | "if_game",
+                    ..
+                } => {
+                    let (body, next) = bracketed(blocks, index);
+                    index = next;
+                    let passed = self.condition(block, locals)?;
+                    if passed {
+                        self.run(body, locals)?;
+                    }
+                    last_condition = Some(passed);
+                    continue;
+                }
+                Block::Code { block: "else", .. } => {
+                    let (body, next) = bracketed(blocks, index);
+                    index = next;
+                    if last_condition == Some(false) {
+                        self.run(body, locals)?;
+                    }
+                }
+                Block::Code {
+                    block: "repeat",
+                    items,
+                    action,
+                    ..
+                } => {
+                    let (body, next) = bracketed(blocks, index);
+                    index = next;
+                    self.repeat(action, items, body, locals)?;
+                }
+                Block::Code {
+                    block: "set_var",
+                    items,
+                    action,
+                    ..
+                } => self.set_variable(action, items, locals)?,
+                Block::Code {
+                    block: "player_action",
+                    items,
+                    action,
+                    ..
+                } if action == "SendMessage" => {
+                    let values = self.arguments(items, locals)?;
+                    let separator = match tag(items, "Text Value Merging") {
+                        Some("No spaces") => "",
+                        _ => " ",
+                    };
+                    let message = values
+                        .iter()
+                        .map(Value::to_string)
+                        .collect::<Vec<_>>()
+                        .join(separator);
+                    self.messages.push(message);
+                }
+                // Other actions change the world, which isn't simulated.
+                Block::Code { .. } => {}
+                Block::FunctionCall { data, span, .. } => {
+                    let Some(body) = self.functions.get(data.as_str()) else {
+                        return Err(Failure::new(format!("Unknown function `{data}`")));
+                    };
+                    self.call(body, locals, *span)?;
+                }
+                // Processes run right away, as if they never wait.
+                Block::ProcessCall {
+                    data, span, items, ..
+                } => {
+                    let Some(body) = self.processes.get(data.as_str()) else {
+                        return Err(Failure::new(format!("Unknown process `{data}`")));
+                    };
+                    let local_variables = items.iter().find_map(|item| match &item.item {
+                        ItemData::Tag { tag, option, .. } if tag == "Local Variables" => {
+                            Some(option.as_str())
+                        }
+                        _ => None,
+                    });
+                    match local_variables {
+                        Some("Share") => self.call(body, locals, *span)?,
+                        Some("Copy") => self.call(body, &mut locals.clone(), *span)?,
+                        _ => self.call(body, &mut Variables::new(), *span)?,
+                    }
+                }
+                Block::Assert { assertion, span } => {
+                    self.check(assertion, locals).map_err(|failure| Failure {
+                        span: Some(*span),
+                        ..failure
+                    })?;
+                }
+                Block::EventDefinition { .. }
+                | Block::FunctionDefinition { .. }
+                | Block::ProcessDefinition { .. }
+                | Block::TestDefinition { .. }
+                | Block::Bracket { .. } => {}
+            }
+            last_condition = None;
+        }
+        Ok(())
+    }
+
+    /// Runs the body of a function or process.
+    fn call(
+        &mut self,
+        body: &[&Block<'a>],
+        locals: &mut Variables,
+        span: SimpleSpan,
+    ) -> Result<(), Failure> {
+        self.depth += 1;
+        let result = self.run(body, locals);
+        self.depth -= 1;
+        // Blocks in functions don't know where they were written, so failures point at the call in the test.
+        result.map_err(|failure| Failure {
+            span: failure.span.or((self.depth == 0).then_some(span)),
+            ..failure
+        })
+    }
+
+    /// Checks the condition of an If block.
+    fn condition(&self, block: &Block<'a>, locals: &Variables) -> Result<bool, Failure> {
+        let Block::Code {
+            block: "if_var",
+            items,
+            action,
+            inverted,
+            ..
+        } = block
+        else {
+            return Err(needs_server(block));
+        };
+        let passed = match action.as_str() {
+            "VarExists" => match items.first().map(|item| &item.item) {
+                Some(ItemData::Variable { scope, name }) => {
+                    self.variables(scope, locals).contains_key(name)
+                }
+                _ => false,
+            },
+            _ => compare(action, &self.arguments(items, locals)?, items)?,
+        };
+        Ok(passed != (*inverted == "NOT"))
+    }
+
+    /// Runs the body of a Repeat block.
+    fn repeat(
+        &mut self,
+        action: &str,
+        items: &[Item],
+        body: &[&Block<'a>],
+        locals: &mut Variables,
+    ) -> Result<(), Failure> {
+        let (counter, values) = match items.first() {
+            Some(
+                item @ Item {
+                    item: ItemData::Variable { .. },
+                    ..
+                },
+            ) => (Some(&item.item), self.arguments(&items[1..], locals)?),
+            _ => (None, self.arguments(items, locals)?),
+        };
+        match (action, counter) {
+            ("Multiple", _) => {
+                let [times] = numbers(action, &values)?[..] else {
+                    return Err(Failure::new("`Multiple` needs how many times to repeat"));
+                };
+                for time in 1..=times as usize {
+                    if let Some(counter) = counter {
+                        self.set(counter, Value::Number(time as f64), locals)?;
+                    }
+                    self.run(body, locals)?;
+                }
+            }
+            ("Range", Some(counter)) => {
+                let (start, end, step) = match numbers(action, &values)?[..] {
+                    [start, end] => (start, end, 1.0),
+                    [start, end, step] if step > 0.0 => (start, end, step),
+                    _ => {
+                        return Err(Failure::new(
+                            "`Range` needs a start, an end and a step above 0",
+                        ))
+                    }
+                };
+                let mut current = start;
+                while current <= end {
+                    self.set(counter, Value::Number(current), locals)?;
+                    self.run(body, locals)?;
+                    current += step;
+                }
+            }
+            ("ForEach", Some(counter)) => {
+                let Some(Value::List(list)) = values.first() else {
+                    return Err(Failure::new("`ForEach` needs a list"));
+                };
+                for value in list.clone() {
+                    self.set(counter, value, locals)?;
+                    self.run(body, locals)?;
+                }
+            }
+            _ => {
+                return Err(Failure::new(format!(
+                    "The interpreter can't run the repeat `{action}` yet"
+                )))
+            }
+        }
+        Ok(())
+    }
+
+    /// Runs a Set Variable block.
+    fn set_variable(
+        &mut self,
+        action: &str,
+        items: &[Item],
+        locals: &mut Variables,
+    ) -> Result<(), Failure> {
+        let Some(target) = items.first() else {
+            return Err(Failure::new(format!("`{action}` needs a variable to set")));
+        };
+        let values = self.arguments(&items[1..], locals)?;
+        let current = || self.value(&target.item, locals);
+        let value = match action {
+            "=" => values.into_iter().next().unwrap_or(Value::Number(0.0)),
+            "+" => Value::Number(numbers(action, &values)?.iter().sum()),
+            "x" => Value::Number(numbers(action, &values)?.iter().product()),
+            "-" | "/" => {
+                let numbers = numbers(action, &values)?;
+                let Some((&first, rest)) = numbers.split_first() else {
+                    return Err(Failure::new(format!("`{action}` needs numbers")));
+                };
+                let mut result = first;
+                for &number in rest {
+                    if action == "-" {
+                        result -= number;
+                    } else if number == 0.0 {
+                        return Err(Failure::new("Division by zero"));
+                    } else {
+                        result /= number;
+                    }
+                }
+                if tag(items, "Division Mode") == Some("Floor result") {
+                    result = result.floor();
+                }
+                Value::Number(result)
+            }
+            "%" => match numbers(action, &values)?[..] {
+                [_, 0.0] => return Err(Failure::new("Division by zero")),
+                [dividend, divisor] => Value::Number(dividend % divisor),
+                _ => return Err(Failure::new("`%` needs two numbers")),
+            },
+            "+=" | "-=" => {
+                let change = numbers(action, &values)?.iter().sum::<f64>();
+                let [current] = numbers(action, &[current()?])?[..] else {
+                    unreachable!()
+                };
+                Value::Number(if action == "+=" {
+                    current + change
+                } else {
+                    current - change
+                })
+            }
+            "CreateList" => Value::List(values),
+            "AppendValue" => match current()? {
+                Value::List(mut list) => {
+                    list.extend(values);
+                    Value::List(list)
+                }
+                other => {
+                    return Err(Failure::new(format!(
+                        "`AppendValue` needs a list, found {}",
+                        other.quoted()
+                    )))
+                }
+            },
+            "Text" => {
+                let separator = match tag(items, "Text Value Merging") {
+                    Some("Add spaces") => " ",
+                    _ => "",
+                };
+                Value::Text(
+                    values
+                        .iter()
+                        .map(Value::to_string)
+                        .collect::<Vec<_>>()
+                        .join(separator),
+                )
+            }
+            "ListLength" => match values.first() {
+                Some(Value::List(list)) => Value::Number(list.len() as f64),
+                _ => return Err(Failure::new("`ListLength` needs a list")),
+            },
+            "GetListValue" => match &values[..] {
+                [Value::List(list), Value::Number(index)] => {
+                    // DF lists start at 1.
+                    match list.get((*index as usize).wrapping_sub(1)) {
+                        Some(value) => value.clone(),
+                        None => {
+                            return Err(Failure::new(format!(
+                                "Index {} is out of bounds for a list of {} values",
+                                Value::Number(*index),
+                                list.len()
+                            )))
+                        }
+                    }
+                }
+                _ => return Err(Failure::new("`GetListValue` needs a list and an index")),
+            },
+            _ => {
+                return Err(Failure::new(format!(
+                    "The interpreter can't run the set variable action `{action}` yet"
+                )))
+            }
+        };
+        self.set(&target.item, value, locals)
+    }
+
+    /// Checks an assertion.
+    fn check(&self, assertion: &Assertion<'a>, locals: &Variables) -> Result<(), Failure> {
+        match assertion {
+            Assertion::Condition(condition) => {
+                if self.condition(condition, locals)? {
+                    return Ok(());
+                }
+                let Block::Code {
+                    items,
+                    action,
+                    inverted,
+                    ..
+                } = condition.as_ref()
+                else {
+                    unreachable!("assertions are If Variable blocks");
+                };
+                let values = self
+                    .arguments(items, locals)?
+                    .iter()
+                    .map(Value::quoted)
+                    .collect::<Vec<_>>();
+                let not = if *inverted == "NOT" { "not " } else { "" };
+                Err(Failure::new(format!(
+                    "Assertion failed: {not}{} {action} {}",
+                    values.first().cloned().unwrap_or_default(),
+                    values.get(1..).unwrap_or_default().join(", ")
+                )))
+            }
+            Assertion::Sent(expected) => {
+                let expected = self.interpolate(expected, locals);
+                if self.messages.contains(&expected) {
+                    return Ok(());
+                }
+                let sent = match &self.messages[..] {
+                    [] => "nothing".to_string(),
+                    messages => messages
+                        .iter()
+                        .map(|message| format!("{message:?}"))
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                };
+                Err(Failure::new(format!(
+                    "The player wasn't sent {expected:?}, they were sent {sent}"
+                )))
+            }
+        }
+    }
+
+    /// The values of the items of a block, leaving out its tags.
+    fn arguments(&self, items: &[Item], locals: &Variables) -> Result<Vec<Value>, Failure> {
+        items
+            .iter()
+            .filter(|item| !matches!(item.item, ItemData::Tag { .. } | ItemData::NoData))
+            .map(|item| self.value(&item.item, locals))
+            .collect()
+    }
+
+    /// The value of an item. Variables that were never set are 0, like in DF.
+    fn value(&self, data: &ItemData, locals: &Variables) -> Result<Value, Failure> {
+        Ok(match data {
+            ItemData::Number { data } => Value::Number(number(*data)),
+            ItemData::Text { data } => Value::Text(self.interpolate(data, locals)),
+            ItemData::Variable { scope, name } => self
+                .variables(scope, locals)
+                .get(name)
+                .cloned()
+                .unwrap_or(Value::Number(0.0)),
+            ItemData::GameValue { kind, .. } => {
+                return Err(Failure::new(format!(
+                    "The game value `{kind}` needs a server, so it can't be used in a test"
+                )))
+            }
+            other => Value::Item(other.clone()),
+        })
+    }
+
+    /// Replaces the `%var(name)` placeholders in a text with the values of the variables.
+    /// Like in DF, the local variable is used if there is one, then the game variable, then the saved one.
+    fn interpolate(&self, text: &str, locals: &Variables) -> String {
+        let mut out = String::new();
+        let mut rest = text;
+        while let Some(start) = rest.find("%var(") {
+            let Some(length) = rest[start..].find(')') else {
+                break;
+            };
+            let name = &rest[start + "%var(".len()..start + length];
+            let value = [locals, &self.game, &self.saved]
+                .iter()
+                .find_map(|variables| variables.get(name))
+                .cloned()
+                .unwrap_or(Value::Number(0.0));
+            out.push_str(&rest[..start]);
+            out.push_str(&value.to_string());
+            rest = &rest[start + length + 1..];
+        }
+        out.push_str(rest);
+        out
+    }
+
+    fn variables<'v>(&'v self, scope: &VariableScope, locals: &'v Variables) -> &'v Variables {
+        match scope {
+            VariableScope::Local => locals,
+            VariableScope::Unsaved => &self.game,
+            VariableScope::Saved => &self.saved,
+        }
+    }
+
+    fn set(&mut self, var: &ItemData, value: Value, locals: &mut Variables) -> Result<(), Failure> {
+        let ItemData::Variable { scope, name } = var else {
+            return Err(Failure::new(format!(
+                "Expected a variable to set, found {}",
+                var.describe()
+            )));
+        };
+        let variables = match scope {
+            VariableScope::Local => locals,
+            VariableScope::Unsaved => &mut self.game,
+            VariableScope::Saved => &mut self.saved,
+        };
+        variables.insert(name.clone(), value);
+        Ok(())
+    }
+}
+
+/// Checks an If Variable condition on the values of its items.
+fn compare(action: &str, values: &[Value], items: &[Item]) -> Result<bool, Failure> {
+    let Some((first, rest)) = values.split_first() else {
+        return Err(Failure::new(format!("`{action}` needs a value to check")));
+    };
+    let ignore_case = tag(items, "Ignore Case") == Some("True");
+    let text = |value: &Value| {
+        let text = value.to_string();
+        if ignore_case {
+            text.to_lowercase()
+        } else {
+            text
+        }
+    };
+    Ok(match action {
+        "=" => rest.iter().any(|value| first.equals(value)),
+        "!=" => !rest.iter().any(|value| first.equals(value)),
+        "<" | ">" | "<=" | ">=" | "InRange" => match (action, &numbers(action, values)?[..]) {
+            ("<", [a, b]) => a < b,
+            (">", [a, b]) => a > b,
+            ("<=", [a, b]) => a <= b,
+            (">=", [a, b]) => a >= b,
+            ("InRange", [value, low, high]) => low <= value && value <= high,
+            _ => {
+                return Err(Failure::new(format!(
+                    "Wrong number of values for `{action}`"
+                )))
+            }
+        },
+        "ListContains" => match first {
+            Value::List(list) => rest
+                .iter()
+                .any(|value| list.iter().any(|item| item.equals(value))),
+            _ => return Err(Failure::new("`ListContains` needs a list")),
+        },
+        "Contains" => rest.iter().any(|value| text(first).contains(&text(value))),
+        "StartsWith" => rest
+            .iter()
+            .any(|value| text(first).starts_with(&text(value))),
+        "EndsWith" => rest.iter().any(|value| text(first).ends_with(&text(value))),
+        _ => {
+            return Err(Failure::new(format!(
+                "The interpreter can't check the if variable condition `{action}` yet"
+            )))
+        }
+    })
+}
+
+/// The values as numbers, if they all are.
+fn numbers(action: &str, values: &[Value]) -> Result<Vec<f64>, Failure> {
+    values
+        .iter()
+        .map(|value| match value {
+            Value::Number(number) => Ok(*number),
+            other => Err(Failure::new(format!(
+                "`{action}` needs numbers, found {}",
+                other.quoted()
+            ))),
+        })
+        .collect()
+}
+
+/// A number as it was written: `0.1` in a script is 0.1, not the closest f32 to it.
+fn number(data: f32) -> f64 {
+    data.to_string().parse().unwrap_or(data as f64)
+}
+
+/// The selected option of a tag of a block.
+fn tag<'i>(items: &'i [Item], name: &str) -> Option<&'i str> {
+    items.iter().find_map(|item| match &item.item {
+        ItemData::Tag { tag, option, .. } if tag == name => Some(option.as_str()),
+        _ => None,
+    })
+}
+
+/// The blocks between the opening bracket at `start` and its closing bracket,
+/// along with the index after the closing bracket.
+fn bracketed<'b, 'a>(blocks: &'b [&'b Block<'a>], start: usize) -> (&'b [&'b Block<'a>], usize) {
+    let mut depth = 0;
+    for (index, block) in blocks.iter().enumerate().skip(start) {
+        if let Block::Bracket { direct, .. } = block {
+            match direct {
+                BracketDirection::Open => depth += 1,
+                BracketDirection::Close => depth -= 1,
+            }
+            if depth == 0 {
+                return (&blocks[start + 1..index], index + 1);
+            }
+        }
+    }
+    (&blocks[(start + 1).min(blocks.len())..], blocks.len())
+}
+
+/// The failure of a block that can't run without a server.
+fn needs_server(block: &Block) -> Failure {
+    let Block::Code { block, action, .. } = block else {
+        return Failure::new("This block can't run in a test");
+    };
+    let kind = ACTION_DUMP
+        .codeblocks
+        .iter()
+        .find(|codeblock| codeblock.identifier == *block)
+        .map(|codeblock| codeblock.name.to_lowercase())
+        .unwrap_or_else(|| block.to_string());
+    Failure::new(format!(
+        "The {kind} condition `{action}` needs a server, so it can't be checked in a test"
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{modules::script_files, test_dir::TestDir};
+
+    /// Writes a script into a fresh directory, and runs its tests.
+    /// Returns the name of each test, along with its failure if it failed.
+    fn run(name: &str, source: &str) -> Vec<(String, Option<String>)> {
+        let root = TestDir::with_files(&format!("test-{name}"), &[("main.bls", source)]);
+        let project = Project::load(&root, &[], &script_files(&root).unwrap()).unwrap();
+        let modules = project.compile().expect("failed to compile");
+        run_tests(&project, &modules)
+            .into_iter()
+            .map(|result| (result.name, result.outcome.err().map(|e| e.message)))
+            .collect()
+    }
+
+    #[test]
+    fn arithmetic_and_conditions() {
+        let results = run(
+            "arithmetic",
+            r#"
+            func score() {
+                var game.score = game.score * 2 + 1;
+                if var game.score = with(3) {
+                    player.SendMessage("Three!");
+                };
+                else {
+                    player.SendMessage("Score: {game.score}");
+                };
+            }
+            test "score doubles" {
+                var game.score = 1;
+                call score();
+                assert var game.score = with(3);
+                assert sent("Three!");
+                call score();
+                assert sent("Score: 7");
+                assert !var game.score = with(3);
+            }
+            test "fails" {
+                var local.x = 0.1 + 0.2;
+                assert var local.x = with(0.3);
+                assert var local.x = with(1);
+            }
+            "#,
+        );
+        assert_eq!(
+            results,
+            vec![
+                ("score doubles".to_string(), None),
+                (
+                    "fails".to_string(),
+                    Some("Assertion failed: 0.3 = 1".to_string())
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn repeats_and_lists() {
+        let results = run(
+            "repeats",
+            r#"
+            test "sum" {
+                var local.total = 0;
+                repeat Range(local.i, 1, 4) {
+                    var local.total = local.total + local.i;
+                };
+                assert var local.total = with(10);
+                var local.list = CreateList(2, 3);
+                repeat ForEach(local.item, local.list) {
+                    player.SendMessage("item", local.item);
+                };
+                assert sent("item 3");
+                assert sent("item 4");
+            }
+            "#,
+        );
+        assert_eq!(
+            results[0].1.as_deref(),
+            Some(r#"The player wasn't sent "item 4", they were sent "item 2", "item 3""#)
+        );
+    }
+
+    #[test]
+    fn server_conditions_fail_the_test() {
+        let results = run(
+            "server",
+            r#"
+            test "sneaking" {
+                if player.IsSneaking {};
+            }
+            "#,
+        );
+        assert_eq!(
+            results[0].1.as_deref(),
+            Some("The if player condition `IsSneaking` needs a server, so it can't be checked in a test")
+        );
+    }
+}
@@ -1,187 +1,860 @@
 use ariadne::*;
-use chumsky::Parser;
-use codegen::{block::Block, misc::process_block_vec};
+use cli::{Command, Options};
+use codeclient::{CodeClient, CodeClientError, CodeClientMode, CODECLIENT_ADDRESS};
+use codegen::split::split_line;
 
-use std::{env, io, io::Write, net::TcpStream};
+use error::{CompileError, ErrorCode};
+use manifest::{Manifest, Plot, Target, MANIFEST_FILE};
+use modules::{Module, Package, Project};
+use output::BuiltLine;
+use recode::{RecodeClient, RecodeError, RECODE_ADDRESS};
+use std::{
+    env, io,
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+use watch::Watch;
 
+mod cli;
+mod codeclient;
 mod codegen;
+mod decompiler;
+mod error;
+mod interpreter;
+mod manifest;
+mod modules;
+mod output;
+mod packages;
 mod parser;
+mod recode;
+#[cfg(test)]
+mod test_dir;
+mod watch;
 
-fn main() -> io::Result<()> {
-    let args = env::args().collect::<Vec<_>>();
+/// The command prefix.
+const PREFIX: &str = "shulker";
+
+/// Exits with 0 if the command succeeded, 1 if it failed (e.g. the scripts have errors or a test failed),
+/// and 2 if the command line itself is wrong.
+fn main() -> ExitCode {
     env::set_var("RUST_BACKTRACE", "1");
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    let (command, mut options) = match cli::parse(&args) {
+        Ok(parsed) => parsed,
+        Err(message) => {
+            eprintln!("error: {message}\nRun `{PREFIX} help` to see the commands and options.");
+            return ExitCode::from(2);
+        }
+    };
+    // See https://no-color.org.
+    options.color &= env::var_os("NO_COLOR").is_none();
+
+    match run(command, &options) {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(e) => {
+            eprintln!("{} {e}", options.paint("31;1", "error:"));
+            ExitCode::FAILURE
+        }
+    }
+}
 
+/// Runs a command. Returns whether it succeeded.
+fn run(command: Command, options: &Options) -> io::Result<bool> {
     let start = std::time::Instant::now();
 
-    let prefix = "shulker"; // cmd prefix
-
-    if let Some(arg) = args.get(1) {
-        match arg.as_str() {
-            "build" => {
-                println!("\t\x1b[32;1mBuilding\x1b[0m from `./scripts` directory.");
-                let paths = std::fs::read_dir("./scripts")?;
-                for path in paths {
-                    let display = path
-                        .expect("somehow doesnt exist")
-                        .path()
-                        .display()
-                        .to_string();
-                    println!("\t\x1b[32;1mBuilding\x1b[0m `{display}`.");
-                    let file =
-                        std::fs::read_to_string(display.clone()).expect("somehow doesnt exist");
-                    process_inputs(&file, &display, CompileTarget::Recode);
-                    std::thread::sleep(std::time::Duration::from_millis(100));
+    match command {
+        Command::Init { name } => {
+            let dir = env::current_dir()?;
+            let name = name.unwrap_or_else(|| {
+                dir.file_name()
+                    .map(|name| name.to_string_lossy().to_string())
+                    .unwrap_or_default()
+            });
+            let manifest = Manifest {
+                plot: Plot {
+                    name,
+                    author: env::var("USER").unwrap_or_default(),
+                    ..Default::default()
+                },
+                ..Default::default()
+            };
+            manifest::init(&dir, &manifest)?;
+            options.status(
+                "Created",
+                format!("`{}` in `{}`.", manifest.plot.name, dir.display()),
+            );
+        }
+        Command::Build { inputs } => {
+            let manifest = Manifest::find(Path::new("."))?.unwrap_or_default();
+            let (root, entries) = sources(&manifest, &inputs)?;
+            let line_length = manifest.plot.size.line_length();
+            let target = compile_target(&manifest, options);
+            options.status(
+                "Building",
+                format!("from `{}` directory.", shown_root(&root)),
+            );
+            if !process_inputs(
+                &root,
+                &installed_packages()?,
+                &entries,
+                target,
+                line_length,
+                options,
+            )? {
+                return Ok(false);
+            }
+            options.status("Finished", format!("in {}ms.", start.elapsed().as_millis()));
+        }
+        Command::Watch { inputs } => {
+            let manifest = Manifest::find(Path::new("."))?.unwrap_or_default();
+            let target = compile_target(&manifest, options);
+            return watch_inputs(&manifest, &inputs, &installed_packages()?, target, options);
+        }
+        Command::BuildTest { inputs } => {
+            let manifest = Manifest::find(Path::new("."))?.unwrap_or_default();
+            let (root, entries) = sources(&manifest, &inputs)?;
+            return test_inputs(&root, &installed_packages()?, &entries, options);
+        }
+        Command::Add { source, name } => {
+            let dir = Path::new(".");
+            let mut manifest = Manifest::find(dir)?.ok_or_else(|| {
+                io::Error::other(format!(
+                    "There's no `{MANIFEST_FILE}` here, run `{PREFIX} init` first."
+                ))
+            })?;
+            let (name, dependency) = packages::dependency(&source, name.as_deref());
+            manifest.dependencies.insert(name.clone(), dependency);
+            // Installing first means a dependency that can't be read isn't added to the manifest.
+            packages::install(dir, &manifest, &[&name])?;
+            std::fs::write(dir.join(MANIFEST_FILE), manifest.to_toml())?;
+            options.status("Added", format!("`{name}`."));
+        }
+        Command::Decompile { input } => decompile(&input)?,
+        Command::Version => {
+            //find the toml file - that has the version
+            //3rd line has version as `version = "[version]"
+            let cargo_toml = include_str!("../Cargo.toml");
+            match cargo_toml.lines().nth(2) {
+                Some(line) => {
+                    let line = line.strip_prefix("version = ").unwrap_or(line);
+                    if line.is_empty() {
+                        return Err(std::io::Error::other(
+                            "Did not find the correct version line.",
+                        ));
+                    }
+                    let vers = line.trim_matches('"');
+                    println!("Current version: {vers}")
+                }
+                None => {
+                    return Err(std::io::Error::other(
+                        "Could not find a valid `Cargo.toml` file to fetch a version from.",
+                    ))
                 }
+            }
+        }
+        Command::Recode => {
+            println!("Recode on modrinth: https://modrinth.com/mod/recode");
+            println!("Recode on github: https://github.com/homchom/recode");
+        }
+        Command::Docs => {
+            println!("The documentation is currently in beta!");
+            println!("You may view its repo here: https://github.com/BlackstoneDF/BlackstoneDF.github.io")
+        }
+        Command::Help => help_message(PREFIX, options),
+    }
+
+    Ok(true)
+}
+
+/// Where built code lines go: the target given on the command line, or the one in the manifest.
+fn compile_target(manifest: &Manifest, options: &Options) -> CompileTarget {
+    // Giving an output directory is asking for files.
+    let target = options.target.unwrap_or(match options.out {
+        Some(_) => Target::File,
+        None => manifest.build.target,
+    });
+    match target {
+        Target::Recode => CompileTarget::Recode,
+        Target::Stdout => CompileTarget::Stdout,
+        Target::File => CompileTarget::File {
+            out: options
+                .out
+                .clone()
+                .unwrap_or_else(|| manifest.build.out.clone()),
+            plot: manifest.plot.clone(),
+        },
+        Target::CodeClient => CompileTarget::CodeClient {
+            mode: manifest.build.codeclient,
+            plot: manifest.plot.clone(),
+        },
+    }
+}
+
+/// The scripts directory and the scripts to build.
+/// Those are the given scripts (a directory stands for every script in it),
+/// or every script of the project if none are given.
+fn sources(manifest: &Manifest, inputs: &[PathBuf]) -> io::Result<(PathBuf, Vec<PathBuf>)> {
+    let Some(first) = inputs.first() else {
+        let root = manifest.build.source.clone();
+        let entries = modules::script_files(&root)?;
+        return Ok((root, entries));
+    };
+    let mut entries = vec![];
+    for input in inputs {
+        if input.is_dir() {
+            entries.append(&mut modules::script_files(input)?);
+        } else if input.is_file() {
+            entries.push(input.clone());
+        } else {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("`{}` doesn't exist", input.display()),
+            ));
+        }
+    }
+    // The root is found for a script in a directory, so a `scripts` directory is its own root.
+    let root = if first.is_dir() {
+        modules::scripts_root(&first.join("_"))
+    } else {
+        modules::scripts_root(first)
+    };
+    Ok((root, entries))
+}
 
-                let dur = start.elapsed();
-                println!("time taken: {}ms", dur.as_millis());
+/// How the root of the scripts is shown in the status, which is `.` when it's the current directory.
+fn shown_root(root: &Path) -> String {
+    if root.as_os_str().is_empty() {
+        ".".to_string()
+    } else {
+        root.display().to_string()
+    }
+}
+
+/// Builds the given scripts, along with the scripts they `use`, and sends every code line to the target.
+/// Code lines longer than `line_length` are split up.
+/// Returns whether it built without errors.
+fn process_inputs(
+    root: &Path,
+    packages: &[Package],
+    entries: &[PathBuf],
+    target: CompileTarget,
+    line_length: usize,
+    options: &Options,
+) -> io::Result<bool> {
+    let project = Project::load(root, packages, entries)?;
+
+    let sent = project.compile().and_then(|modules| {
+        let lines = build_lines(&project, modules, line_length)?;
+        let lines = lines
+            .iter()
+            .map(|(file, line)| (project.files[*file].path.as_path(), line))
+            .collect::<Vec<_>>();
+        Sender::new(target).deliver(&lines, options)
+    });
+    match sent {
+        Ok(()) => Ok(true),
+        Err(errors) => {
+            report_failure(&project, errors, options);
+            Ok(false)
+        }
+    }
+}
+
+/// Builds the given scripts like `process_inputs`, then keeps checking them for changes until stopped.
+/// When scripts change, they're built again along with the scripts that `use` them,
+/// and only the code lines that changed are sent (the `file` target still writes every line).
+/// Scripts with errors are built again on the next change, along with whatever changed then.
+/// Scripts whose code lines couldn't be read or delivered are tried again after a while, even if they don't change.
+fn watch_inputs(
+    manifest: &Manifest,
+    inputs: &[PathBuf],
+    packages: &[Package],
+    target: CompileTarget,
+    options: &Options,
+) -> io::Result<bool> {
+    let line_length = manifest.plot.size.line_length();
+    let writes_every_line = matches!(target, CompileTarget::File { .. });
+    let mut sender = Sender::new(target);
+    let mut watch = Watch::default();
+    // Every script being built, to know which ones `use` the ones that changed.
+    let (root, entries) = sources(manifest, inputs)?;
+    let mut graph = Project::load(&root, packages, &entries)?;
+    let mut first = true;
+    // When to try again if the last build couldn't be read or delivered.
+    let mut retry_at = None;
+    loop {
+        // A script can be removed or replaced while it's read, so errors here are only reported.
+        let (root, entries) = match sources(manifest, inputs) {
+            Ok(sources) => sources,
+            Err(e) => {
+                report_io(e, options);
+                std::thread::sleep(watch::POLL_INTERVAL);
+                continue;
             }
-            "build-one" => {
-                if let Some(arg2) = args.get(1) {
-                    let file = std::fs::read_to_string(arg2)?;
-                    process_inputs(&file, arg2, CompileTarget::Recode);
-                } else {
-                    println!("There needs to be a string for a file path after the command,\ne.g `{prefix} build-one /foo/bar.bls`\n");
-                }
+        };
+        // The scripts that are `use`d are watched too, even when they weren't given.
+        let mut watched = entries.clone();
+        watched.extend(graph.files.iter().map(|file| file.path.clone()));
+        let changed = watch.poll(watched.iter().map(PathBuf::as_path));
+        let retrying = retry_at.is_some_and(|at| std::time::Instant::now() >= at);
+        if changed.is_empty() && !retrying {
+            std::thread::sleep(watch::POLL_INTERVAL);
+            continue;
+        }
+        retry_at = None;
+
+        let start = std::time::Instant::now();
+        if first {
+            options.status(
+                "Building",
+                format!("from `{}` directory.", shown_root(&root)),
+            );
+        } else if changed.is_empty() {
+            options.status("Retrying", "the scripts that weren't delivered.");
+        } else {
+            for path in &changed {
+                let change = if path.exists() { "Changed" } else { "Removed" };
+                options.status(change, format!("`{}`.", path.display()));
             }
-            "build-stdout" => {
-                if let Some(arg2) = args.get(1) {
-                    let file = std::fs::read_to_string(arg2)?;
-                    process_inputs(&file, arg2, CompileTarget::Stdout);
-                } else {
-                    println!("There needs to be a string for a file path after the command,\ne.g `{prefix} build-stdout /foo/bar.bls`\n");
-                }
+        }
+        let loaded = match Project::load(&root, packages, &entries) {
+            Ok(loaded) => loaded,
+            Err(e) => {
+                report_io(e, options);
+                retry_at = Some(start + watch::RETRY_INTERVAL);
+                continue;
             }
-            "version" => {
-                //find the toml file - that has the version
-                //3rd line has version as `version = "[version]"
-                let cargo_toml = include_str!("../Cargo.toml");
-                match cargo_toml.lines().nth(2) {
-                    Some(line) => {
-                        let line = line.strip_prefix("version = ").unwrap_or(line);
-                        if line.is_empty() {
-                            return Err(std::io::Error::new(
-                                std::io::ErrorKind::Other,
-                                "Did not find the correct version line.",
-                            ));
+        };
+        let previous = std::mem::replace(&mut graph, loaded);
+        let pending = watch.pending();
+        // A script that `use`d a removed script is only found in the project as it was before.
+        let mut affected = previous.dependents(&pending);
+        affected.extend(pending.iter().cloned());
+        let affected = graph.dependents(&affected);
+        for path in pending.iter().filter(|path| !affected.contains(path)) {
+            watch.removed(path);
+        }
+
+        let project = match Project::load(&root, packages, &affected) {
+            Ok(project) => project,
+            Err(e) => {
+                report_io(e, options);
+                retry_at = Some(start + watch::RETRY_INTERVAL);
+                continue;
+            }
+        };
+        let built = project
+            .compile()
+            .and_then(|modules| build_lines(&project, modules, line_length));
+        match built {
+            // Only scripts were removed, and nothing `use`d them.
+            _ if affected.is_empty() => {}
+            Ok(built) => {
+                // The scripts they `use` are built too, but only to check the calls to them.
+                let by_script = affected
+                    .iter()
+                    .map(|path| {
+                        let lines = built
+                            .iter()
+                            .filter(|(file, _)| project.files[*file].path == *path)
+                            .map(|(_, line)| line.clone())
+                            .collect::<Vec<_>>();
+                        (path, lines)
+                    })
+                    .collect::<Vec<_>>();
+                let mut changed = vec![];
+                for (path, lines) in &by_script {
+                    changed.extend(
+                        watch
+                            .changed(path, lines)
+                            .into_iter()
+                            .map(|line| (path.as_path(), line)),
+                    );
+                }
+                options.status(
+                    "Built",
+                    format!(
+                        "{} script{} in {}ms, {} code line{} changed.",
+                        affected.len(),
+                        if affected.len() == 1 { "" } else { "s" },
+                        start.elapsed().as_millis(),
+                        changed.len(),
+                        if changed.len() == 1 { "" } else { "s" },
+                    ),
+                );
+                let to_send = if writes_every_line {
+                    // The lines of the scripts that weren't built keep what was delivered last.
+                    watch
+                        .lines()
+                        .into_iter()
+                        .filter(|(path, _)| !affected.iter().any(|a| a == path))
+                        .chain(by_script.iter().flat_map(|(path, lines)| {
+                            lines.iter().map(|line| (path.as_path(), line))
+                        }))
+                        .collect::<Vec<_>>()
+                } else {
+                    changed.iter().map(|(path, line)| (*path, line)).collect()
+                };
+                let sent = if to_send.is_empty() {
+                    Ok(())
+                } else {
+                    sender.deliver(&to_send, options)
+                };
+                match sent {
+                    // Only delivered lines are recorded, so the ones that weren't are sent again.
+                    Ok(()) => {
+                        for (path, lines) in by_script {
+                            watch.delivered(path, lines);
                         }
-                        let vers = line.trim_matches('"');
-                        println!("Current version: {vers}")
                     }
-                    None => {
-                        return Err(std::io::Error::new(
-                            std::io::ErrorKind::Other,
-                            "Could not find a valid `Cargo.toml` file to fetch a version from.",
-                        ))
+                    Err(errors) => {
+                        report_failure(&project, errors, options);
+                        retry_at = Some(start + watch::RETRY_INTERVAL);
                     }
                 }
             }
-            "recode" => {
-                println!("Recode on modrinth: https://modrinth.com/mod/recode");
-                println!("Recode on github: https://github.com/homchom/recode");
+            Err(errors) => report_failure(&project, errors, options),
+        }
+        if first {
+            options.status(
+                "Watching",
+                format!("`{}` for changes, press Ctrl+C to stop.", shown_root(&root)),
+            );
+            first = false;
+        }
+    }
+}
+
+/// Reports an error reading the scripts, which stops a build of `watch` but not `watch` itself.
+fn report_io(e: io::Error, options: &Options) {
+    eprintln!("{} {e}", options.paint("31;1", "error:"));
+}
+
+/// Reports the errors that stopped a build, and how many there were.
+fn report_failure(project: &Project, errors: Vec<CompileError>, options: &Options) {
+    let count = errors.len();
+    report(project, errors, options);
+    eprintln!(
+        "{} could not build due to {count} previous error{}",
+        options.paint("31;1", "error:"),
+        if count == 1 { "" } else { "s" }
+    );
+}
+
+/// Every code line of the modules, split into lines no longer than `line_length`,
+/// along with the script it's from, as an index into `Project::files`.
+/// Fails with every line that can't be split to fit.
+fn build_lines(
+    project: &Project,
+    modules: Vec<Module>,
+    line_length: usize,
+) -> Result<Vec<(usize, BuiltLine)>, Vec<CompileError>> {
+    let mut built = vec![];
+    let mut errors = vec![];
+    for module in modules {
+        let file = &project.files[module.file];
+        let mut lines = vec![];
+        for line in module.lines {
+            let line = line.into_iter().flatten().collect::<Vec<_>>();
+            // Every code line starts with a definition, so this can't happen, but there'd be nothing to send.
+            if line.is_empty() {
+                continue;
             }
-            "docs" => {
-                println!("The documentation is currently in beta!");
-                println!("You may view its repo here: https://github.com/BlackstoneDF/BlackstoneDF.github.io")
+            match split_line(line, line_length) {
+                Ok(mut split) => lines.append(&mut split),
+                Err(mut e) => {
+                    e.file = Some(module.file);
+                    errors.push(e);
+                }
             }
-            _ => help_message(prefix), //"help" is included in the catch-all
         }
+        // Lines are counted after splitting, so the helpers of a line are numbered right after it.
+        for (number, line) in lines.iter().enumerate() {
+            built.push((module.file, BuiltLine::new(&file.name, number + 1, line)));
+        }
+    }
+    if errors.is_empty() {
+        Ok(built)
     } else {
-        help_message(prefix);
+        Err(errors)
     }
-
-    Ok(())
 }
 
-fn compile_with_recode(vector: Vec<Block>, _name: String) {
-    let s = process_block_vec(vector);
-    let send =
-        r#"{"type": "template","source": "Blackstone","data":"{'name':'my name','data':'%s%'}"}"#;
-    let send = send.replace("%s%", &s);
-    // let send = send.replace("%n%", &name);
-    let mut stream = TcpStream::connect("localhost:31372").expect("failed to connect");
-    stream
-        .write_all(send.as_bytes())
-        .expect("failed to write all");
+/// Sends built code lines to a target.
+/// The connections to `recode` and CodeClient are kept open between deliveries,
+/// so `watch` only connects (and asks for `/auth`) once.
+///   - CompileTarget `target`: Where the lines go
+///   - Option<RecodeClient> `recode`: The connection to `recode`, once there is one
+///   - Option<CodeClient> `codeclient`: The connection to CodeClient, once there is one
+struct Sender {
+    target: CompileTarget,
+    recode: Option<RecodeClient>,
+    codeclient: Option<CodeClient>,
 }
 
-fn compile_to_console(vector: Vec<Block>) {
-    println!("{}", process_block_vec(vector));
-    println!("--------------");
-    println!("Paste the above into DF to get it as a template.");
+impl Sender {
+    fn new(target: CompileTarget) -> Sender {
+        Sender {
+            target,
+            recode: None,
+            codeclient: None,
+        }
+    }
+
+    /// Sends code lines, each along with the path of the script it's from.
+    /// The `file` target writes only the lines it's given, and removes the files of any others.
+    fn deliver(
+        &mut self,
+        lines: &[(&Path, &BuiltLine)],
+        options: &Options,
+    ) -> Result<(), Vec<CompileError>> {
+        let mut errors = vec![];
+        match &self.target {
+            CompileTarget::Recode => {
+                let client = match &mut self.recode {
+                    Some(client) => client,
+                    None => self
+                        .recode
+                        .insert(RecodeClient::connect(RECODE_ADDRESS).map_err(|e| {
+                            vec![CompileError::new(
+                                ErrorCode::Send,
+                                format!("Failed to connect, {e}"),
+                            )]
+                        })?),
+                };
+                let (mut delivered, mut total) = (0, 0);
+                'scripts: for (path, lines) in by_script(lines) {
+                    options.status("Sending", format!("`{}` to `recode`.", path.display()));
+                    for line in lines {
+                        let code = encode(path, line).map_err(|e| vec![e])?;
+                        total += 1;
+                        match client.send(&line.name, &code) {
+                            Ok(()) => {
+                                delivered += 1;
+                                options.detail(format!("Delivered `{}`.", line.name));
+                            }
+                            Err(e) => {
+                                // A rejected line doesn't stop the others, but a broken connection does.
+                                let reachable = matches!(e, RecodeError::Rejected(_));
+                                let outcome = match e {
+                                    RecodeError::Unconfirmed(_) => "may not have been delivered",
+                                    _ => "wasn't delivered",
+                                };
+                                errors.push(CompileError::new(
+                                    ErrorCode::Send,
+                                    format!(
+                                        "`{}` from `{}` {outcome}, {e}",
+                                        line.name,
+                                        path.display()
+                                    ),
+                                ));
+                                if !reachable {
+                                    break 'scripts;
+                                }
+                            }
+                        }
+                    }
+                }
+                options.status(
+                    "Delivered",
+                    format!("{delivered} of {total} code lines to `recode`."),
+                );
+            }
+            CompileTarget::Stdout => {
+                for (path, lines) in by_script(lines) {
+                    options.status("Printing", format!("`{}`.", path.display()));
+                    for line in lines {
+                        println!("{}", encode(path, line).map_err(|e| vec![e])?);
+                        options.status(
+                            "Printed",
+                            "Paste the line above into DF to get it as a template.",
+                        );
+                    }
+                }
+            }
+            CompileTarget::File { out, plot } => {
+                for (path, _) in by_script(lines) {
+                    options.detail(format!("Built `{}`.", path.display()));
+                }
+                options.status(
+                    "Writing",
+                    format!("{} code lines to `{}`.", lines.len(), out.display()),
+                );
+                let lines = lines
+                    .iter()
+                    .map(|(_, line)| (*line).clone())
+                    .collect::<Vec<_>>();
+                output::write(out, plot, &lines).map_err(|e| {
+                    vec![CompileError::new(
+                        ErrorCode::Send,
+                        format!("Failed to write to `{}`: {e}", out.display()),
+                    )]
+                })?;
+            }
+            CompileTarget::CodeClient { mode, plot } => {
+                for (path, _) in by_script(lines) {
+                    options.detail(format!("Built `{}`.", path.display()));
+                }
+                let sent = lines
+                    .iter()
+                    .map(|(_, line)| line.envelope(output::author(plot)))
+                    .collect::<io::Result<Vec<_>>>()
+                    .map_err(CodeClientError::from)
+                    .and_then(|templates| {
+                        let client = match &mut self.codeclient {
+                            Some(client) => client,
+                            None => {
+                                options.status(
+                                    "Connecting",
+                                    "to CodeClient, run `/auth` in Minecraft to allow it.",
+                                );
+                                self.codeclient
+                                    .insert(CodeClient::connect(CODECLIENT_ADDRESS, *mode)?)
+                            }
+                        };
+                        client.deliver(&templates)
+                    });
+                match sent {
+                    Ok(()) => options.status(
+                        match mode {
+                            CodeClientMode::Place => "Placed",
+                            CodeClientMode::Give => "Gave",
+                        },
+                        format!("{} code lines with CodeClient.", lines.len()),
+                    ),
+                    Err(e) => {
+                        // The connection can't be trusted anymore, so the next delivery makes a new one.
+                        self.codeclient = None;
+                        errors.push(CompileError::new(
+                            ErrorCode::Send,
+                            format!("Failed to send the code lines, {e}"),
+                        ))
+                    }
+                }
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
 }
 
-/*
+/// Code lines grouped by the script they're from, keeping their order.
+fn by_script<'a>(lines: &[(&'a Path, &'a BuiltLine)]) -> Vec<(&'a Path, Vec<&'a BuiltLine>)> {
+    let mut scripts: Vec<(&Path, Vec<&BuiltLine>)> = vec![];
+    for &(path, line) in lines {
+        match scripts.last_mut() {
+            Some((last, lines)) if *last == path => lines.push(line),
+            _ => scripts.push((path, vec![line])),
+        }
+    }
+    scripts
+}
 
-let vector = vector.into_iter().flatten().collect::<Vec<_>>();
+/// Turns a code line into template data.
+fn encode(path: &Path, line: &BuiltLine) -> Result<String, CompileError> {
+    line.code().map_err(|e| {
+        CompileError::new(
+            ErrorCode::Codegen,
+            format!(
+                "Failed to compress the template of `{}` in `{}`: {e}",
+                line.name,
+                path.display()
+            ),
+        )
+    })
+}
 
-            let _ = vector.get(0).expect("codeless?");
+/// Runs the tests in the given scripts, and in the scripts they `use`.
+/// Returns whether every test passed.
+fn test_inputs(
+    root: &Path,
+    packages: &[Package],
+    entries: &[PathBuf],
+    options: &Options,
+) -> io::Result<bool> {
+    let project = Project::load(root, packages, entries)?;
+    let modules = match project.compile() {
+        Ok(modules) => modules,
+        Err(errors) => {
+            report(&project, errors, options);
+            return Ok(false);
+        }
+    };
 
-            let name = path.to_string();
-            println!("\t\x1b[32;1mSending\x1b[0m `{path}` to client.");
-            match target {
-                CompileTarget::Recode => compile_with_recode(vector, name),
-                CompileTarget::Stdout => compile_to_console(vector),
+    let results = interpreter::run_tests(&project, &modules);
+    options.status(
+        "Running",
+        format!(
+            "{} test{}.",
+            results.len(),
+            if results.len() == 1 { "" } else { "s" }
+        ),
+    );
+    let mut passed = 0;
+    let mut failures = vec![];
+    for result in results {
+        let name = format!("{}: {}", project.files[result.file].name, result.name);
+        match result.outcome {
+            Ok(()) => {
+                println!("test {name} ... {}", options.paint("32", "ok"));
+                passed += 1;
             }
- */
-fn process_inputs(input: &str, path: &str, target: CompileTarget) {
-    println!("input: {input}");
-    let result = parser::parse::parser().parse(input);
+            Err(failure) => {
+                println!("test {name} ... {}", options.paint("31", "FAILED"));
+                failures.push(failure);
+            }
+        }
+    }
+    let failed = failures.len();
+    report(&project, failures, options);
+    println!(
+        "\ntest result: {}. {passed} passed; {failed} failed",
+        if failed == 0 {
+            options.paint("32", "ok")
+        } else {
+            options.paint("31", "FAILED")
+        }
+    );
+    Ok(failed == 0)
+}
 
-    match result.clone().into_result() {
-        Ok(vector) => {
-            println!("\t\x1b[32;1mSending\x1b[0m `{path}` to client.");
-            for subvector in vector {
-                let subvector = subvector.into_iter().flatten().collect::<Vec<_>>();
-                let _ = subvector.get(0).expect("codeless?");
-                let name = path.to_string();
+/// Prints errors in the order they appear in the scripts of a project, pointing at where they are.
+fn report(project: &Project, mut errors: Vec<CompileError>, options: &Options) {
+    errors.sort_by_key(|e| (e.file, e.span.map(|span| span.start)));
+    for e in errors {
+        let (Some(file), Some(span)) = (e.file, e.span) else {
+            let location = e
+                .file
+                .map(|file| format!(" (while building `{}`)", project.files[file].path.display()))
+                .unwrap_or_default();
+            let kind = options.paint("31", format!("[{}] Error:", e.code));
+            eprintln!("{kind} {}{location}", e.message);
+            continue;
+        };
+        let file = &project.files[file];
+        let path = file.path.display().to_string();
+        Report::build(ReportKind::Error, path.as_str(), span.start)
+            .with_code(e.code)
+            .with_config(Config::default().with_color(options.color))
+            .with_message(e.message)
+            .with_label(Label::new((path.as_str(), span.start..span.end)).with_color(Color::Red))
+            .finish()
+            .eprint((path.as_str(), Source::from(&file.source)))
+            .expect("failed to print?");
+    }
+}
 
-                match target {
-                    CompileTarget::Recode => compile_with_recode(subvector, name),
-                    CompileTarget::Stdout => compile_to_console(subvector),
+/// The libraries of the project in the current directory, copied into `deps/`.
+fn installed_packages() -> io::Result<Vec<Package>> {
+    match Manifest::find(Path::new("."))? {
+        Some(manifest) => packages::install(Path::new("."), &manifest, &[]),
+        None => Ok(vec![]),
+    }
+}
+
+/// Prints the source of every template in a file, or of the template given directly.
+/// Lines of a file that aren't templates (like notes between them) are skipped.
+fn decompile(input: &str) -> io::Result<()> {
+    let contents = match std::fs::read_to_string(input) {
+        Ok(contents) => contents,
+        Err(_) => input.to_string(),
+    };
+    // A whole file can be a single template, like the pretty JSON the `file` target writes.
+    let templates = if decompiler::decode_template(&contents).is_ok() {
+        vec![contents.as_str()]
+    } else {
+        contents
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .collect()
+    };
+    let mut last_error = None;
+    let mut found = false;
+    for line in templates {
+        match decompiler::decode_template(line) {
+            Ok(decoded) => {
+                if found {
+                    println!();
                 }
+                found = true;
+                if let Some(name) = decoded.name {
+                    println!("// {name}");
+                }
+                print!("{}", decompiler::decompile(&decoded.template));
             }
+            Err(e) => last_error = Some(e),
         }
-        Err(errors) => {
-            println!("it's error");
-            for e in errors {
-                Report::build(ReportKind::Error, (), e.span().start)
-                    .with_message(e.reason().to_string())
-                    .with_label(Label::new(e.span().start..e.span().end).with_color(Color::Red))
-                    .finish()
-                    .print(Source::from(input))
-                    .expect("failed to print?");
-            }
-        }
+    }
+    match (found, last_error) {
+        (false, Some(e)) => Err(io::Error::other(e)),
+        (false, None) => Err(io::Error::other("No templates found.")),
+        _ => Ok(()),
     }
 }
 
-fn help_message(prefix: &str) {
-    let bold = "\x1b[39;1m";
-    let reset = "\x1b[0m";
-    println!("{bold}");
+fn help_message(prefix: &str, options: &Options) {
+    let bold = |text: &str| options.paint("39;1", text);
+    // Entries are padded before they're styled, so the descriptions line up.
+    let entry = |text: &str| options.paint("39;1", format!("{text:<28}"));
+    // The header is printed bold, so the style is left open around it.
+    println!("{}", if options.color { "\x1b[39;1m" } else { "" });
     blackstone_header();
-    println!("{reset}");
+    println!("{}", if options.color { "\x1b[0m" } else { "" });
 
     println!(
         r#"
 Blackstone's compiler & build tooling
 
-{bold}Usage:{reset} {prefix} [commands]
+{} {prefix} <command> [options] [scripts...]
 
-{bold}Built-in commands:{reset}
-    {bold}version{reset}                     Get the current version of Blackstone
-    {bold}init{reset}                        Initialize a new Blackstone environment in your current directory
-    {bold}build-one [script]{reset}          Builds the code provided & sends it via `recode` mod
-    {bold}build{reset}                       Builds all code in the `scripts` directory & sends it via `recode` mod
-    {bold}build-stdout [script]{reset}       Sends the code data to the console instead of to `recode`
+{}
+    {}Get the current version of Blackstone
+    {}Initialize a new Blackstone environment in your current directory
+    {}Builds the scripts given (or every script in the project) & sends them to the target
+    {}Builds like `build`, then rebuilds whenever the scripts change & sends the code lines that changed
+    {}Builds the scripts given & sends them via `recode` mod
+    {}Builds the scripts given & prints the code data to the console
                                 Useful if you don't have `recode` installed
-    {bold}build-test{reset}                  Run the tests in the code. (Coming soon!)
-    {bold}add [package]{reset}               Add an external package to your scripts
-    {bold}recode{reset}                      Gives a link to the `recode` mod, for ease of use with Blackstone
-    {bold}help{reset}                        Shows this message
-    {bold}docs{reset}                        View documentation (Coming soon!)
-    "#
+    {}Runs the `test` blocks of the scripts given (or of the project), without needing DF
+    {}Add a library (a project directory or a tarball of one) to your scripts
+    {}Turns template data (or a file of it) back into Blackstone code
+    {}Gives a link to the `recode` mod, for ease of use with Blackstone
+    {}Shows this message
+    {}View documentation (Coming soon!)
+
+{}
+    {}Where built code goes: `recode`, `codeclient`, `stdout` or `file`, instead of the one in `blackstone.toml`
+    {}The directory the `file` target writes to (this picks the `file` target)
+    {}Print more of what's happening
+    {}Only print the output and errors
+    {}Don't color the output (setting `NO_COLOR` does the same)
+
+Exits with 1 if the scripts have errors or a test fails, and with 2 if the command is wrong.
+    "#,
+        bold("Usage:"),
+        bold("Built-in commands:"),
+        entry("version"),
+        entry("init [name]"),
+        entry("build [scripts...]"),
+        entry("watch [scripts...]"),
+        entry("build-one <scripts...>"),
+        entry("build-stdout <scripts...>"),
+        entry("build-test [scripts...]"),
+        entry("add <path> [name]"),
+        entry("decompile <template>"),
+        entry("recode"),
+        entry("help"),
+        entry("docs"),
+        bold("Options:"),
+        entry("-t, --target <target>"),
+        entry("-o, --out <directory>"),
+        entry("-v, --verbose"),
+        entry("-q, --quiet"),
+        entry("--no-color"),
     );
 }
 #[allow(dead_code)]
@@ -199,4 +872,14 @@ fn blackstone_header() {
 enum CompileTarget {
     Recode,
     Stdout,
+    /// Writes every format of every code line into `out`, for the plot.
+    File {
+        out: PathBuf,
+        plot: Plot,
+    },
+    /// Gives or places every code line through CodeClient.
+    CodeClient {
+        mode: CodeClientMode,
+        plot: Plot,
+    },
 }
@@ -0,0 +1,200 @@
+//! The `blackstone.toml` manifest of a project, and `init` to create a new project.
+
+use std::{
+    collections::BTreeMap,
+    io,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::codeclient::CodeClientMode;
+
+/// The file name of the manifest, at the root of a project.
+pub const MANIFEST_FILE: &str = "blackstone.toml";
+
+/// The script `init` creates, so a new project has something to build.
+const SAMPLE_SCRIPT: &str = r#"event player.Join {
+    player.SendMessage("Hello, world!");
+}
+"#;
+
+/// The manifest of a project.
+///   - Plot `plot`: What the project is
+///   - Build `build`: How the project is built
+///   - BTreeMap<String, Dependency> `dependencies`: The libraries the project uses, by the name its scripts `use` them with
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
+pub struct Manifest {
+    pub plot: Plot,
+    #[serde(default)]
+    pub build: Build,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub dependencies: BTreeMap<String, Dependency>,
+}
+
+/// The `[plot]` section of the manifest.
+///   - String `name`: The name of the plot
+///   - String `author`: Who made it
+///   - PlotSize `size`: How big the plot is, which decides how long its code lines can be
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
+pub struct Plot {
+    pub name: String,
+    #[serde(default)]
+    pub author: String,
+    #[serde(default)]
+    pub size: PlotSize,
+}
+
+/// The size of a plot. Bigger plots have longer code lines.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum PlotSize {
+    #[default]
+    Basic,
+    Large,
+    Massive,
+    Mega,
+}
+
+impl PlotSize {
+    /// How many blocks long a code line of the plot can be.
+    pub fn line_length(self) -> usize {
+        match self {
+            PlotSize::Basic => 50,
+            PlotSize::Large => 100,
+            PlotSize::Massive => 300,
+            PlotSize::Mega => 1000,
+        }
+    }
+}
+
+/// The `[build]` section of the manifest. Every field can be left out.
+///   - Target `target`: Where built code lines are sent
+///   - PathBuf `source`: The directory holding the scripts, relative to the manifest
+///   - PathBuf `out`: The directory the `file` target writes to, relative to the manifest
+///   - CodeClientMode `codeclient`: What the `codeclient` target does with built code lines
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Build {
+    pub target: Target,
+    pub source: PathBuf,
+    pub out: PathBuf,
+    pub codeclient: CodeClientMode,
+}
+
+impl Default for Build {
+    fn default() -> Self {
+        Self {
+            target: Target::Recode,
+            source: PathBuf::from("scripts"),
+            out: PathBuf::from("out"),
+            codeclient: CodeClientMode::Place,
+        }
+    }
+}
+
+/// Where built code lines are sent.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Target {
+    /// To the `recode` mod, which gives them to the player as templates.
+    Recode,
+    /// Printed to the console.
+    Stdout,
+    /// Written to files.
+    File,
+    /// To the CodeClient mod, which places them in the plot or gives them to the player.
+    CodeClient,
+}
+
+/// Where a library comes from. Paths are relative to the manifest.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Dependency {
+    /// A project directory, e.g. `{ path = "../entity_utils" }`.
+    Path { path: PathBuf },
+    /// A (gzipped) tarball of a project, like the ones `git archive` makes, e.g. `{ tarball = "vendor/entity_utils.tar.gz" }`.
+    Tarball { tarball: PathBuf },
+}
+
+impl Manifest {
+    /// Reads the manifest in a directory.
+    /// Returns None if there isn't one, so projects without a manifest build with the defaults.
+    pub fn find(dir: &Path) -> io::Result<Option<Manifest>> {
+        let path = dir.join(MANIFEST_FILE);
+        if !path.is_file() {
+            return Ok(None);
+        }
+        let contents = std::fs::read_to_string(&path)?;
+        toml::from_str(&contents)
+            .map(Some)
+            .map_err(|e| io::Error::other(format!("Invalid `{}`: {e}", path.display())))
+    }
+
+    /// The manifest as TOML.
+    pub fn to_toml(&self) -> String {
+        toml::to_string(self).expect("failed to serialize manifest")
+    }
+}
+
+/// Creates a new project in a directory: a manifest, a `scripts` directory with a sample script, and a `.gitignore`.
+/// Fails without writing anything if the directory already has a manifest.
+pub fn init(dir: &Path, manifest: &Manifest) -> io::Result<()> {
+    let manifest_path = dir.join(MANIFEST_FILE);
+    if manifest_path.exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("`{}` already exists", manifest_path.display()),
+        ));
+    }
+    let source = dir.join(&manifest.build.source);
+    std::fs::create_dir_all(&source)?;
+    let sample = source.join("join.bls");
+    if !sample.exists() {
+        std::fs::write(sample, SAMPLE_SCRIPT)?;
+    }
+    let gitignore = dir.join(".gitignore");
+    if !gitignore.exists() {
+        std::fs::write(gitignore, "/out/\n/deps/\n")?;
+    }
+    std::fs::write(manifest_path, manifest.to_toml())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_dir::TestDir;
+
+    #[test]
+    fn build_section_is_optional() {
+        let manifest: Manifest = toml::from_str("[plot]\nname = \"Islands\"").unwrap();
+        assert_eq!(manifest.plot.name, "Islands");
+        assert_eq!(manifest.plot.size, PlotSize::Basic);
+        assert_eq!(manifest.build, Build::default());
+    }
+
+    #[test]
+    fn init_creates_a_project_that_builds() {
+        let dir = TestDir::new("init");
+        let manifest = Manifest {
+            plot: Plot {
+                name: "Islands".to_string(),
+                author: "Endistic".to_string(),
+                size: PlotSize::Large,
+            },
+            build: Build {
+                target: Target::Stdout,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        init(&dir, &manifest).unwrap();
+        assert_eq!(Manifest::find(&dir).unwrap(), Some(manifest.clone()));
+        assert!(init(&dir, &manifest).is_err());
+
+        let source = dir.join(&manifest.build.source);
+        let scripts = crate::modules::script_files(&source).unwrap();
+        let project = crate::modules::Project::load(&source, &[], &scripts).unwrap();
+        assert!(project.compile().is_ok());
+    }
+}
@@ -0,0 +1,662 @@
+//! Projects made of several scripts that `use` each other, and the scripts of the libraries they depend on.
+//! DF function and process names are shared by the whole plot, so they're checked across every script of a project.
+//! Names defined by a library are prefixed with the library's name, so two libraries can't collide.
+
+use std::{
+    collections::{HashMap, HashSet},
+    io,
+    path::{Path, PathBuf},
+};
+
+use chumsky::{span::SimpleSpan, Parser};
+
+use crate::{
+    codegen::block::Block,
+    error::{CompileError, ErrorCode},
+    parser::parse::{file_parser, imports_parser, CodeLines, Import},
+};
+
+/// The file extensions of scripts, in the order they're looked for.
+pub const SCRIPT_EXTENSIONS: [&str; 2] = ["bls", "blst"];
+
+/// A library a project depends on, whose scripts are `use`d with its name in front, e.g. `use entity_utils::spawn;`.
+///   - String `name`: The name of the library
+///   - PathBuf `root`: The directory holding its scripts
+#[derive(Debug, Clone, PartialEq)]
+pub struct Package {
+    pub name: String,
+    pub root: PathBuf,
+}
+
+/// A script of a project.
+///   - String `name`: The module path of the script, e.g. `utils::math`, or `entity_utils::spawn` for a library's script
+///   - PathBuf `path`: Where the script was read from
+///   - Option<usize> `package`: The library the script is from, as an index into `Project::packages`, or None if it's the project's own
+///   - String `source`: The contents of the script
+///   - Vec<Import> `imports`: The `use` statements of the script
+///   - Vec<usize> `dependencies`: The scripts it `use`s, as indices into `Project::files`
+#[derive(Debug, Clone)]
+pub struct SourceFile {
+    pub name: String,
+    pub path: PathBuf,
+    pub package: Option<usize>,
+    pub source: String,
+    pub imports: Vec<Import>,
+    pub dependencies: Vec<usize>,
+}
+
+/// A parsed script.
+///   - usize `file`: The script, as an index into `Project::files`
+///   - CodeLines `lines`: The code lines the script defines
+///   - CodeLines `tests`: The `test` blocks of the script, which are kept out of `lines` since they aren't sent to DF
+#[derive(Debug, Clone)]
+pub struct Module<'a> {
+    pub file: usize,
+    pub lines: CodeLines<'a>,
+    pub tests: CodeLines<'a>,
+}
+
+/// The scripts being built, along with every script they `use`.
+/// Errors found while following the `use`s (missing scripts, cycles) are kept in `errors`.
+#[derive(Debug, Default)]
+pub struct Project {
+    root: PathBuf,
+    pub packages: Vec<Package>,
+    pub files: Vec<SourceFile>,
+    pub errors: Vec<CompileError>,
+    /// The index of each script that was read, by its canonical path.
+    indices: HashMap<PathBuf, usize>,
+}
+
+impl Project {
+    /// Reads the given scripts and every script they `use`, directly or not.
+    /// `use` paths are relative to `root`, e.g. `use utils::math;` is `root/utils/math.bls`,
+    /// unless they start with the name of a package, e.g. `use entity_utils::spawn;`.
+    pub fn load(root: &Path, packages: &[Package], entries: &[PathBuf]) -> io::Result<Project> {
+        let mut project = Project {
+            root: root.to_path_buf(),
+            packages: packages.to_vec(),
+            ..Default::default()
+        };
+        let mut done = HashSet::new();
+        for entry in entries {
+            let file = project.read(entry, None)?;
+            project.follow_imports(file, &mut vec![], &mut done);
+        }
+        Ok(project)
+    }
+
+    /// Parses every script, then checks that no name is defined twice and that every call can be resolved.
+    /// A script can call what it defines itself, and what the scripts it `use`s define.
+    pub fn compile(&self) -> Result<Vec<Module<'_>>, Vec<CompileError>> {
+        let mut errors = self.errors.clone();
+        let mut modules = vec![];
+        for (file, source) in self.files.iter().enumerate() {
+            match file_parser().parse(&source.source).into_result() {
+                Ok((_, lines)) => {
+                    let (tests, lines): (CodeLines, CodeLines) =
+                        lines.into_iter().partition(|line| {
+                            matches!(line.first(), Some(Some(Block::TestDefinition { .. })))
+                        });
+                    for block in lines.iter().flatten().flatten() {
+                        if let Block::Assert { span, .. } = block {
+                            errors.push(CompileError::at(
+                                ErrorCode::MisplacedAssert,
+                                file,
+                                *span,
+                                "`assert` can only be used in a `test`",
+                            ));
+                        }
+                    }
+                    modules.push(Module { file, lines, tests })
+                }
+                Err(parse_errors) => errors.extend(
+                    parse_errors
+                        .iter()
+                        .map(|e| CompileError::from_parser(file, e)),
+                ),
+            }
+        }
+        // Names can only be resolved once every definition is known.
+        if errors.is_empty() {
+            errors = self.resolve_names(&mut modules);
+        }
+        if errors.is_empty() {
+            Ok(modules)
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// The paths of the given scripts and of every script that `use`s one of them, directly or not,
+    /// in the order they were read. Paths that aren't scripts of the project are left out.
+    pub fn dependents(&self, paths: &[PathBuf]) -> Vec<PathBuf> {
+        let mut found = self
+            .files
+            .iter()
+            .map(|file| paths.contains(&file.path))
+            .collect::<Vec<_>>();
+        // Each pass finds the scripts that `use` one found in the pass before, until there are none left.
+        loop {
+            let next = self
+                .files
+                .iter()
+                .enumerate()
+                .filter(|&(index, file)| {
+                    !found[index] && file.dependencies.iter().any(|&d| found[d])
+                })
+                .map(|(index, _)| index)
+                .collect::<Vec<_>>();
+            if next.is_empty() {
+                break;
+            }
+            for index in next {
+                found[index] = true;
+            }
+        }
+        self.files
+            .iter()
+            .zip(found)
+            .filter(|(_, found)| *found)
+            .map(|(file, _)| file.path.clone())
+            .collect()
+    }
+
+    /// Reads a script if it hasn't been read yet, returning its index.
+    fn read(&mut self, path: &Path, package: Option<usize>) -> io::Result<usize> {
+        let key = path.canonicalize()?;
+        if let Some(&index) = self.indices.get(&key) {
+            return Ok(index);
+        }
+        let source = std::fs::read_to_string(path)?;
+        // Only the `use`s are needed for now; the rest of the script is parsed (and its errors reported) when compiling.
+        let imports = imports_parser()
+            .lazy()
+            .parse(&source)
+            .into_output()
+            .unwrap_or_default();
+        self.files.push(SourceFile {
+            name: self.module_name(path, package),
+            path: path.to_path_buf(),
+            package,
+            source,
+            imports,
+            dependencies: vec![],
+        });
+        self.indices.insert(key, self.files.len() - 1);
+        Ok(self.files.len() - 1)
+    }
+
+    /// Reads the scripts a script `use`s, and the ones those `use`, reporting cycles along the way.
+    /// `stack` holds the scripts whose imports are being followed, which is the path to the current one.
+    fn follow_imports(&mut self, file: usize, stack: &mut Vec<usize>, done: &mut HashSet<usize>) {
+        if !done.insert(file) {
+            return;
+        }
+        stack.push(file);
+        for import in self.files[file].imports.clone() {
+            let Some((path, package)) = self.resolve(file, &import.path) else {
+                self.error(
+                    ErrorCode::MissingScript,
+                    file,
+                    import.span,
+                    format!(
+                        "Can't find the script `{}`, expected it at `{}`",
+                        import.path.join("::"),
+                        self.root_of(self.files[file].package)
+                            .join(import.path.join("/"))
+                            .with_extension(SCRIPT_EXTENSIONS[0])
+                            .display()
+                    ),
+                );
+                continue;
+            };
+            let dependency = match self.read(&path, package) {
+                Ok(dependency) => dependency,
+                Err(e) => {
+                    self.error(
+                        ErrorCode::MissingScript,
+                        file,
+                        import.span,
+                        format!("Failed to read `{}`: {e}", path.display()),
+                    );
+                    continue;
+                }
+            };
+            if let Some(position) = stack.iter().position(|&f| f == dependency) {
+                let cycle = stack[position..]
+                    .iter()
+                    .chain([&dependency])
+                    .map(|&f| self.files[f].name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(" -> ");
+                self.error(
+                    ErrorCode::ImportCycle,
+                    file,
+                    import.span,
+                    format!("Scripts can't `use` each other in a cycle: {cycle}"),
+                );
+                continue;
+            }
+            self.files[file].dependencies.push(dependency);
+            self.follow_imports(dependency, stack, done);
+        }
+        stack.pop();
+    }
+
+    /// Finds the script a `use` in a script refers to, and the package it's in.
+    /// Paths are relative to the root of the package the script is in, or start with the name of a package.
+    fn resolve(&self, file: usize, path: &[String]) -> Option<(PathBuf, Option<usize>)> {
+        let package = self.files[file].package;
+        let find = |base: PathBuf| {
+            SCRIPT_EXTENSIONS
+                .iter()
+                .map(|extension| base.with_extension(extension))
+                .find(|candidate| candidate.is_file())
+        };
+        if let Some(found) = find(self.root_of(package).join(path.join("/"))) {
+            return Some((found, package));
+        }
+        let (first, rest) = path.split_first()?;
+        let index = self.packages.iter().position(|p| &p.name == first)?;
+        if rest.is_empty() {
+            return None;
+        }
+        find(self.packages[index].root.join(rest.join("/"))).map(|found| (found, Some(index)))
+    }
+
+    /// The directory `use` paths are relative to in a package, or in the project itself.
+    fn root_of(&self, package: Option<usize>) -> &Path {
+        match package {
+            Some(index) => &self.packages[index].root,
+            None => &self.root,
+        }
+    }
+
+    /// The module path of a script, e.g. `utils::math` for `scripts/utils/math.bls`.
+    /// Scripts of a package start with its name, and scripts outside of the root are named after their file.
+    fn module_name(&self, path: &Path, package: Option<usize>) -> String {
+        let relative = match path.strip_prefix(self.root_of(package)) {
+            Ok(relative) => relative,
+            Err(_) => Path::new(path.file_name().unwrap_or_default()),
+        };
+        package
+            .map(|index| self.packages[index].name.clone())
+            .into_iter()
+            .chain(
+                relative
+                    .with_extension("")
+                    .components()
+                    .map(|component| component.as_os_str().to_string_lossy().to_string()),
+            )
+            .collect::<Vec<_>>()
+            .join("::")
+    }
+
+    /// The name a function or process defined in a script has on the plot.
+    /// Names from a package get its name in front, e.g. `entity_utils::init`.
+    fn mangle(&self, file: usize, name: &str) -> String {
+        match self.files[file].package {
+            Some(index) => format!("{}::{name}", self.packages[index].name),
+            None => name.to_string(),
+        }
+    }
+
+    /// Checks that no name is defined twice, and points every call at the definition it refers to.
+    /// Definitions from packages, and the calls to them, are renamed to their mangled names.
+    fn resolve_names(&self, modules: &mut [Module]) -> Vec<CompileError> {
+        let mut errors = vec![];
+
+        // The scripts each function and process is defined in.
+        let mut functions: HashMap<String, Vec<usize>> = HashMap::new();
+        let mut processes: HashMap<String, Vec<usize>> = HashMap::new();
+        for module in modules.iter() {
+            for block in module
+                .lines
+                .iter()
+                .filter_map(|line| line.first())
+                .flatten()
+            {
+                let (kind, defined, name, span) = match block {
+                    Block::FunctionDefinition { data, span, .. } => {
+                        ("function", &mut functions, data, span)
+                    }
+                    Block::ProcessDefinition { data, span, .. } => {
+                        ("process", &mut processes, data, span)
+                    }
+                    _ => continue,
+                };
+                let files = defined.entry(name.clone()).or_default();
+                // Only names within a package can collide, since the names of packages are mangled.
+                let package = self.files[module.file].package;
+                match files.iter().find(|&&other| self.files[other].package == package) {
+                    Some(&other) => errors.push(CompileError::at(
+                        ErrorCode::DuplicateName,
+                        module.file,
+                        *span,
+                        if other == module.file {
+                            format!("A {kind} named `{name}` is already defined in this script")
+                        } else {
+                            format!(
+                                "A {kind} named `{name}` is already defined in `{}`, and DF {kind} names are shared by the whole plot",
+                                self.files[other].name
+                            )
+                        },
+                    )),
+                    None => files.push(module.file),
+                }
+            }
+        }
+
+        for module in modules.iter_mut() {
+            let file = &self.files[module.file];
+            for block in module
+                .lines
+                .iter_mut()
+                .chain(module.tests.iter_mut())
+                .flatten()
+                .flatten()
+            {
+                let (kind, defined, name, span) = match block {
+                    Block::FunctionCall { data, span, .. } => ("function", &functions, data, *span),
+                    Block::ProcessCall { data, span, .. } => ("process", &processes, data, *span),
+                    _ => continue,
+                };
+                // A name can be qualified by the script defining it, e.g. `utils::math::add`.
+                let (qualifier, short) = match name.rsplit_once("::") {
+                    Some((qualifier, short)) => (Some(qualifier), short),
+                    None => (None, name.as_str()),
+                };
+                let candidates = defined
+                    .get(short)
+                    .into_iter()
+                    .flatten()
+                    .copied()
+                    .filter(|&other| qualifier.is_none_or(|q| self.files[other].name == q))
+                    .collect::<Vec<_>>();
+                let visible = candidates
+                    .iter()
+                    .copied()
+                    .filter(|&other| other == module.file || file.dependencies.contains(&other))
+                    .collect::<Vec<_>>();
+                let message = match visible[..] {
+                    [target] => {
+                        *name = self.mangle(target, short);
+                        continue;
+                    }
+                    // A script's own definitions take precedence over the ones it `use`s.
+                    _ if visible.contains(&module.file) => {
+                        *name = self.mangle(module.file, short);
+                        continue;
+                    }
+                    [] => match candidates.first() {
+                        Some(&other) => format!(
+                            "The {kind} `{short}` is defined in `{module}`, which this script doesn't use. Add `use {module};` to the top of this script",
+                            module = self.files[other].name
+                        ),
+                        None => format!("Unknown {kind} `{name}`"),
+                    },
+                    _ => format!(
+                        "The {kind} `{short}` is defined in more than one script this script uses ({}). Write `call {}::{short}` to pick one",
+                        visible
+                            .iter()
+                            .map(|&other| format!("`{}`", self.files[other].name))
+                            .collect::<Vec<_>>()
+                            .join(", "),
+                        self.files[visible[0]].name,
+                    ),
+                };
+                errors.push(CompileError::at(
+                    ErrorCode::UnresolvedCall,
+                    module.file,
+                    span,
+                    message,
+                ));
+            }
+        }
+
+        for module in modules.iter_mut() {
+            for block in module
+                .lines
+                .iter_mut()
+                .filter_map(|line| line.first_mut())
+                .flatten()
+            {
+                if let Block::FunctionDefinition { data, .. }
+                | Block::ProcessDefinition { data, .. } = block
+                {
+                    *data = self.mangle(module.file, data);
+                }
+            }
+        }
+        errors
+    }
+
+    fn error(&mut self, code: ErrorCode, file: usize, span: SimpleSpan, message: String) {
+        self.errors
+            .push(CompileError::at(code, file, span, message));
+    }
+}
+
+/// Every script in a directory and its subdirectories, in a stable order.
+pub fn script_files(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut files = vec![];
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            files.append(&mut script_files(&path)?);
+        } else if path
+            .extension()
+            .is_some_and(|extension| SCRIPT_EXTENSIONS.iter().any(|e| extension == *e))
+        {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// The directory `use` paths of a script are relative to:
+/// the `scripts` directory it's in, or the directory of the script if it isn't in one.
+pub fn scripts_root(path: &Path) -> PathBuf {
+    path.ancestors()
+        .skip(1)
+        .find(|dir| dir.file_name().is_some_and(|name| name == "scripts"))
+        .or(path.parent())
+        .unwrap_or(Path::new("."))
+        .to_path_buf()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_dir::TestDir;
+
+    /// Writes a project into a fresh directory and loads every script in it.
+    fn load(name: &str, files: &[(&str, &str)]) -> Project {
+        let root = TestDir::with_files(name, files);
+        let entries = script_files(&root).unwrap();
+        Project::load(&root, &[], &entries).unwrap()
+    }
+
+    fn messages(project: &Project) -> Vec<String> {
+        match project.compile() {
+            Ok(_) => vec![],
+            Err(errors) => errors.into_iter().map(|e| e.message).collect(),
+        }
+    }
+
+    #[test]
+    fn calls_resolve_through_use() {
+        let project = load(
+            "resolve",
+            &[
+                (
+                    "main.bls",
+                    "use utils::math;\nevent player.Join { call add(1); }",
+                ),
+                ("utils/math.bls", "func add() {}"),
+            ],
+        );
+        assert_eq!(messages(&project), Vec::<String>::new());
+        let names = project
+            .files
+            .iter()
+            .map(|f| f.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["main", "utils::math"]);
+    }
+
+    #[test]
+    fn unresolved_calls_are_reported() {
+        let project = load(
+            "unresolved",
+            &[
+                (
+                    "main.bls",
+                    "event player.Join { call add(1); start missing(); }",
+                ),
+                ("math.bls", "func add() {}"),
+            ],
+        );
+        assert_eq!(
+            messages(&project),
+            vec![
+                "The function `add` is defined in `math`, which this script doesn't use. Add `use math;` to the top of this script",
+                "Unknown process `missing`",
+            ]
+        );
+    }
+
+    #[test]
+    fn cycles_are_reported() {
+        let project = load(
+            "cycle",
+            &[
+                ("a.bls", "use b;"),
+                ("b.bls", "use c;"),
+                ("c.bls", "use a;"),
+            ],
+        );
+        assert_eq!(
+            messages(&project),
+            vec!["Scripts can't `use` each other in a cycle: a -> b -> c -> a"]
+        );
+        assert_eq!(project.errors[0].file, Some(2));
+    }
+
+    #[test]
+    fn missing_scripts_are_reported() {
+        let project = load("missing", &[("main.bls", "use utils::nope;")]);
+        let errors = messages(&project);
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].starts_with("Can't find the script `utils::nope`"));
+    }
+
+    #[test]
+    fn package_names_are_mangled() {
+        let root = TestDir::with_files(
+            "mangle",
+            &[
+                (
+                    "scripts/main.bls",
+                    "use a::lib;\nuse b::lib;\nevent player.Join { call a::lib::init(); }",
+                ),
+                ("deps/a/lib.bls", "func init() {}"),
+                ("deps/b/lib.bls", "func init() {}"),
+            ],
+        );
+        let packages = ["a", "b"].map(|name| Package {
+            name: name.to_string(),
+            root: root.join("deps").join(name),
+        });
+        let source = root.join("scripts");
+        let project = Project::load(&source, &packages, &script_files(&source).unwrap()).unwrap();
+        let modules = project.compile().unwrap();
+        let data = modules
+            .iter()
+            .flat_map(|module| module.lines.iter().flatten().flatten())
+            .filter_map(|block| match block {
+                Block::FunctionDefinition { data, .. } => Some(format!("func {data}")),
+                Block::FunctionCall { data, .. } => Some(format!("call {data}")),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(data, vec!["call a::init", "func a::init", "func b::init"]);
+    }
+
+    #[test]
+    fn asserts_are_only_allowed_in_tests() {
+        let project = load(
+            "assert",
+            &[(
+                "main.bls",
+                "func f() { assert var local.x = with(1); }\ntest \"t\" { assert var local.x = with(0); }",
+            )],
+        );
+        assert_eq!(
+            messages(&project),
+            vec!["`assert` can only be used in a `test`"]
+        );
+    }
+
+    #[test]
+    fn errors_have_codes() {
+        let codes = |project: Project| {
+            project
+                .compile()
+                .unwrap_err()
+                .into_iter()
+                .map(|e| e.code)
+                .collect::<Vec<_>>()
+        };
+        let project = load(
+            "codes-parse",
+            &[("main.bls", "event player.Join { player.Nope(); }\nfunc")],
+        );
+        assert_eq!(codes(project), vec![ErrorCode::Invalid, ErrorCode::Syntax]);
+        let project = load("codes-call", &[("main.bls", "func f() { call g(); }")]);
+        assert_eq!(codes(project), vec![ErrorCode::UnresolvedCall]);
+    }
+
+    #[test]
+    fn duplicate_functions_are_reported() {
+        let project = load(
+            "duplicate",
+            &[("a.bls", "func greet() {}"), ("b.bls", "func greet() {}")],
+        );
+        assert_eq!(
+            messages(&project),
+            vec!["A function named `greet` is already defined in `a`, and DF function names are shared by the whole plot"]
+        );
+    }
+
+    #[test]
+    fn dependents_follow_use_backwards() {
+        let project = load(
+            "dependents",
+            &[
+                ("main.bls", "use utils::math;\nevent player.Join {}"),
+                ("shop.bls", "event player.Sneak {}"),
+                ("utils/math.bls", "use utils::numbers;\nfunc add() {}"),
+                ("utils/numbers.bls", "func one() {}"),
+            ],
+        );
+        let path = |name: &str| {
+            project
+                .files
+                .iter()
+                .find(|file| file.name == name)
+                .unwrap()
+                .path
+                .clone()
+        };
+        let mut dependents = project.dependents(&[path("utils::numbers")]);
+        dependents.sort();
+        assert_eq!(
+            dependents,
+            vec![path("main"), path("utils::math"), path("utils::numbers")]
+        );
+        assert_eq!(project.dependents(&[path("shop")]), vec![path("shop")]);
+    }
+}
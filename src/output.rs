@@ -0,0 +1,224 @@
+//! The `file` target: every code line written out in each format DF and its mods use,
+//! along with a manifest listing them, so builds can be archived and diffed.
+
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    codegen::{
+        block::Block,
+        misc::{compress, template, template_name},
+        template::Template,
+    },
+    manifest::Plot,
+};
+
+/// The file listing every code line of a build, in the output directory.
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+/// The `version` of the template item envelope.
+const TEMPLATE_VERSION: u32 = 1;
+
+/// A built code line.
+///   - String `script`: The module name of the script defining it, e.g. `utils::math`
+///   - usize `line`: Which code line of the script it is, counting from 1
+///   - String `name`: The name of its template, e.g. `Function » greet`
+///   - Template `template`: The template itself
+#[derive(Debug, Clone, PartialEq)]
+pub struct BuiltLine {
+    pub script: String,
+    pub line: usize,
+    pub name: String,
+    pub template: Template,
+}
+
+impl BuiltLine {
+    pub fn new(script: &str, line: usize, blocks: &[Block]) -> BuiltLine {
+        BuiltLine {
+            script: script.to_string(),
+            line,
+            name: template_name(blocks),
+            template: template(blocks),
+        }
+    }
+
+    /// The gzipped and base64 encoded JSON of the template, which is the data of a template item.
+    pub fn code(&self) -> io::Result<String> {
+        compress(&serde_json::to_string(&self.template).expect("failed to serialize template"))
+    }
+
+    /// The template item envelope DF and `recode` pass templates around in.
+    pub fn envelope(&self, author: &str) -> io::Result<Envelope> {
+        Ok(Envelope {
+            author: author.to_string(),
+            name: self.name.clone(),
+            version: TEMPLATE_VERSION,
+            code: self.code()?,
+        })
+    }
+
+    /// Where the files of the line go in the output directory, without an extension,
+    /// e.g. `utils/math/2` for the second line of `utils::math`.
+    fn stem(&self) -> PathBuf {
+        let mut stem = self.script.split("::").collect::<PathBuf>();
+        stem.push(self.line.to_string());
+        stem
+    }
+}
+
+/// A template item's data: the code of a template along with who made it and what it's called.
+///   - String `author`: Who made the template
+///   - String `name`: The name of the template item
+///   - u32 `version`: The version of the envelope
+///   - String `code`: The gzipped and base64 encoded JSON of the template
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Envelope {
+    pub author: String,
+    pub name: String,
+    pub version: u32,
+    pub code: String,
+}
+
+/// The manifest of a build, written to `manifest.json`.
+///   - String `plot`: The name of the plot
+///   - String `author`: Who made it
+///   - String `compiler`: The version of Blackstone that built it
+///   - Vec<ManifestLine> `lines`: Every code line, in the order they were built
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BuildManifest {
+    pub plot: String,
+    pub author: String,
+    pub compiler: String,
+    pub lines: Vec<ManifestLine>,
+}
+
+/// A code line in the manifest. Paths are relative to the output directory.
+///   - String `script`: The module name of the script defining it
+///   - usize `line`: Which code line of the script it is, counting from 1
+///   - String `name`: The name of its template
+///   - usize `blocks`: How many blocks long it is, brackets included
+///   - PathBuf `json`: The JSON of the template
+///   - PathBuf `code`: The gzipped and base64 encoded JSON
+///   - PathBuf `template`: The template item envelope
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ManifestLine {
+    pub script: String,
+    pub line: usize,
+    pub name: String,
+    pub blocks: usize,
+    pub json: PathBuf,
+    pub code: PathBuf,
+    pub template: PathBuf,
+}
+
+/// Writes every line into the output directory as `<script>/<line>.json` (the template),
+/// `<script>/<line>.txt` (its code) and `<script>/<line>.template.json` (its envelope),
+/// then lists them in `manifest.json`.
+/// The files listed by the manifest of the last build are removed first, so lines that are gone don't linger.
+pub fn write(out: &Path, plot: &Plot, lines: &[BuiltLine]) -> io::Result<()> {
+    let manifest_path = out.join(MANIFEST_FILE);
+    if let Ok(previous) = std::fs::read_to_string(&manifest_path) {
+        if let Ok(previous) = serde_json::from_str::<BuildManifest>(&previous) {
+            for line in previous.lines {
+                for path in [line.json, line.code, line.template] {
+                    let _ = std::fs::remove_file(out.join(path));
+                }
+            }
+        }
+    }
+
+    let author = author(plot);
+    let mut manifest = BuildManifest {
+        plot: plot.name.clone(),
+        author: author.to_string(),
+        compiler: env!("CARGO_PKG_VERSION").to_string(),
+        lines: vec![],
+    };
+    for line in lines {
+        let stem = line.stem();
+        let entry = ManifestLine {
+            script: line.script.clone(),
+            line: line.line,
+            name: line.name.clone(),
+            blocks: line.template.blocks.len(),
+            json: stem.with_extension("json"),
+            code: stem.with_extension("txt"),
+            template: stem.with_extension("template.json"),
+        };
+        std::fs::create_dir_all(out.join(&stem).parent().unwrap_or(out))?;
+        std::fs::write(out.join(&entry.json), pretty(&line.template))?;
+        std::fs::write(out.join(&entry.code), line.code()?)?;
+        std::fs::write(out.join(&entry.template), pretty(&line.envelope(author)?))?;
+        manifest.lines.push(entry);
+    }
+    std::fs::create_dir_all(out)?;
+    std::fs::write(manifest_path, pretty(&manifest))
+}
+
+/// Who the templates of a plot are by, which is Blackstone if the manifest doesn't say.
+pub fn author(plot: &Plot) -> &str {
+    if plot.author.is_empty() {
+        "Blackstone"
+    } else {
+        &plot.author
+    }
+}
+
+/// Pretty JSON with a trailing newline, so the files diff well.
+fn pretty(value: &impl Serialize) -> String {
+    serde_json::to_string_pretty(value).expect("failed to serialize") + "\n"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_dir::TestDir;
+    use chumsky::span::SimpleSpan;
+
+    fn function(name: &str) -> Vec<Block<'static>> {
+        vec![Block::FunctionDefinition {
+            block: "func",
+            data: name.to_string(),
+            docs: vec![],
+            span: SimpleSpan::new(0, 0),
+        }]
+    }
+
+    #[test]
+    fn lines_are_written_in_every_format() {
+        let out = TestDir::new("out");
+        let plot = Plot {
+            name: "Islands".to_string(),
+            author: "Endistic".to_string(),
+            ..Default::default()
+        };
+        let lines = [
+            BuiltLine::new("main", 1, &function("greet")),
+            BuiltLine::new("utils::math", 1, &function("add")),
+        ];
+        write(&out, &plot, &lines).unwrap();
+
+        let manifest: BuildManifest =
+            serde_json::from_str(&std::fs::read_to_string(out.join(MANIFEST_FILE)).unwrap())
+                .unwrap();
+        assert_eq!(manifest.plot, "Islands");
+        assert_eq!(manifest.lines.len(), 2);
+        assert_eq!(manifest.lines[1].json, PathBuf::from("utils/math/1.json"));
+
+        let envelope = std::fs::read_to_string(out.join("utils/math/1.template.json")).unwrap();
+        let decoded = crate::decompiler::decode_template(&envelope).unwrap();
+        assert_eq!(decoded.name.as_deref(), Some("Function » add"));
+        assert_eq!(decoded.template, lines[1].template);
+        let code = std::fs::read_to_string(out.join("utils/math/1.txt")).unwrap();
+        assert_eq!(code, lines[1].code().unwrap());
+
+        // Building again without a line removes its files.
+        write(&out, &plot, &lines[..1]).unwrap();
+        assert!(out.join("main/1.json").exists());
+        assert!(!out.join("utils/math/1.json").exists());
+    }
+}
@@ -0,0 +1,433 @@
+//! The libraries a project depends on: copying their scripts into `deps/`,
+//! and the `blackstone.lock` lockfile pinning what their scripts contained when they were added.
+
+use std::{
+    collections::BTreeMap,
+    io::{self, Read},
+    path::{Component, Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    manifest::{Dependency, Manifest, MANIFEST_FILE},
+    modules::{script_files, Package, SCRIPT_EXTENSIONS},
+};
+
+/// The file name of the lockfile, next to the manifest.
+pub const LOCK_FILE: &str = "blackstone.lock";
+
+/// The directory the scripts of every library are copied into, one subdirectory each.
+pub const DEPS_DIR: &str = "deps";
+
+/// The scripts of a library, by their path relative to its source directory.
+type Scripts = BTreeMap<PathBuf, Vec<u8>>;
+
+/// The lockfile of a project.
+///   - Vec<LockedPackage> `packages`: Every library of the project, written as `[[package]]`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
+pub struct Lockfile {
+    #[serde(default, rename = "package")]
+    pub packages: Vec<LockedPackage>,
+}
+
+/// A library in the lockfile.
+///   - String `name`: The name of the library
+///   - String `source`: Where it comes from, e.g. `path+../entity_utils`
+///   - String `hash`: The hash of its scripts, e.g. `sha256:9f86d0...`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LockedPackage {
+    pub name: String,
+    pub source: String,
+    pub hash: String,
+}
+
+impl Lockfile {
+    /// Reads a lockfile, or returns an empty one if it doesn't exist yet.
+    pub fn read(path: &Path) -> io::Result<Lockfile> {
+        if !path.is_file() {
+            return Ok(Lockfile::default());
+        }
+        toml::from_str(&std::fs::read_to_string(path)?)
+            .map_err(|e| io::Error::other(format!("Invalid `{}`: {e}", path.display())))
+    }
+
+    /// The lockfile as TOML.
+    pub fn to_toml(&self) -> String {
+        format!(
+            "# This file is written by `shulker add`, don't edit it by hand.\n\n{}",
+            toml::to_string(self).expect("failed to serialize lockfile")
+        )
+    }
+}
+
+impl Dependency {
+    /// Where the dependency comes from, as written in the lockfile.
+    fn source(&self) -> String {
+        match self {
+            Dependency::Path { path } => format!("path+{}", path.display()),
+            Dependency::Tarball { tarball } => format!("tarball+{}", tarball.display()),
+        }
+    }
+}
+
+/// Copies the scripts of every library of a project into `deps/`, and writes the lockfile.
+/// A tarball whose scripts changed since they were locked is an error, unless its name is in `update`.
+/// Directories are libraries still being worked on, so their changes are always accepted.
+pub fn install(dir: &Path, manifest: &Manifest, update: &[&str]) -> io::Result<Vec<Package>> {
+    let lock_path = dir.join(LOCK_FILE);
+    let old = Lockfile::read(&lock_path)?;
+    let mut lock = Lockfile::default();
+    let mut packages = vec![];
+    for (name, dependency) in &manifest.dependencies {
+        if !is_identifier(name) {
+            return Err(io::Error::other(format!(
+                "`{name}` can't be the name of a dependency, since scripts `use` it by name. Use letters, digits and `_`"
+            )));
+        }
+        let scripts = fetch(dir, dependency)?;
+        let locked = LockedPackage {
+            name: name.clone(),
+            source: dependency.source(),
+            hash: hash(&scripts),
+        };
+        let previous = old
+            .packages
+            .iter()
+            .find(|p| p.name == locked.name && p.source == locked.source);
+        let changed = previous.is_some_and(|p| p.hash != locked.hash);
+        if let Dependency::Tarball { tarball } = dependency {
+            if changed && !update.contains(&name.as_str()) {
+                return Err(io::Error::other(format!(
+                    "The scripts of `{name}` changed since they were locked. Run `shulker add {}` to update it",
+                    tarball.display()
+                )));
+            }
+        }
+
+        // The copy in `deps/` is only written again if the scripts changed since they were locked.
+        let root = dir.join(DEPS_DIR).join(name);
+        let unchanged = previous.is_some_and(|p| p.hash == locked.hash) && root.is_dir();
+        if !unchanged {
+            if root.exists() {
+                std::fs::remove_dir_all(&root)?;
+            }
+            for (path, contents) in &scripts {
+                let path = root.join(path);
+                if let Some(parent) = path.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                std::fs::write(path, contents)?;
+            }
+        }
+        lock.packages.push(locked);
+        packages.push(Package {
+            name: name.clone(),
+            root,
+        });
+    }
+    if !lock.packages.is_empty() || lock_path.exists() {
+        std::fs::write(lock_path, lock.to_toml())?;
+    }
+    Ok(packages)
+}
+
+/// Reads the scripts of a library.
+fn fetch(dir: &Path, dependency: &Dependency) -> io::Result<Scripts> {
+    match dependency {
+        Dependency::Path { path } => {
+            let root = dir.join(path);
+            let source = match Manifest::find(&root)? {
+                Some(manifest) => root.join(manifest.build.source),
+                None => root,
+            };
+            let mut scripts = Scripts::new();
+            for file in script_files(&source)? {
+                let relative = file
+                    .strip_prefix(&source)
+                    .expect("script outside of its directory")
+                    .to_path_buf();
+                scripts.insert(relative, std::fs::read(file)?);
+            }
+            Ok(scripts)
+        }
+        Dependency::Tarball { tarball } => read_tarball(&dir.join(tarball)),
+    }
+}
+
+/// Reads the scripts of a library from a tarball, which can be gzipped.
+/// `git archive --prefix` puts everything in one directory, so that directory is used as the root if there's no manifest outside of it.
+fn read_tarball(path: &Path) -> io::Result<Scripts> {
+    let mut bytes = std::fs::read(path)?;
+    if bytes.starts_with(&[0x1f, 0x8b]) {
+        let mut decoded = vec![];
+        libflate::gzip::Decoder::new(bytes.as_slice())?.read_to_end(&mut decoded)?;
+        bytes = decoded;
+    }
+    let mut files = BTreeMap::new();
+    for entry in tar::Archive::new(bytes.as_slice()).entries()? {
+        let mut entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        // The scripts are copied into `deps/<name>`, so a path like `../x.bls` or `/x.bls` could write anywhere.
+        // Only `./` is allowed besides names, since `tar -c .` starts every path with it.
+        let raw = entry.path()?.to_path_buf();
+        let components = raw
+            .components()
+            .filter(|component| *component != Component::CurDir);
+        if !components
+            .clone()
+            .all(|c| matches!(c, Component::Normal(_)))
+        {
+            return Err(io::Error::other(format!(
+                "`{}` has a file outside of it, `{}`",
+                path.display(),
+                raw.display()
+            )));
+        }
+        let file = components.collect::<PathBuf>();
+        let mut contents = vec![];
+        entry.read_to_end(&mut contents)?;
+        files.insert(file, contents);
+    }
+
+    let mut root = PathBuf::new();
+    if !files.contains_key(Path::new(MANIFEST_FILE)) {
+        // A file at the top isn't in a directory, so it's only the root if nothing is.
+        let mut tops = files.keys().map(|path| {
+            let mut components = path.components();
+            components.next().filter(|_| components.next().is_some())
+        });
+        if let Some(Some(top)) = tops.next() {
+            if tops.all(|other| other == Some(top)) {
+                root = PathBuf::from(top.as_os_str());
+            }
+        }
+    }
+    let source = match files.get(&root.join(MANIFEST_FILE)) {
+        Some(manifest) => {
+            let manifest: Manifest =
+                toml::from_str(&String::from_utf8_lossy(manifest)).map_err(|e| {
+                    io::Error::other(format!("Invalid manifest in `{}`: {e}", path.display()))
+                })?;
+            root.join(manifest.build.source)
+        }
+        None => root,
+    };
+
+    Ok(files
+        .into_iter()
+        .filter(|(path, _)| {
+            path.extension()
+                .is_some_and(|extension| SCRIPT_EXTENSIONS.iter().any(|e| extension == *e))
+        })
+        .filter_map(|(path, contents)| {
+            let relative = path.strip_prefix(&source).ok()?.to_path_buf();
+            Some((relative, contents))
+        })
+        .collect())
+}
+
+/// The SHA-256 hash of a library's scripts, covering both their paths and their contents.
+fn hash(scripts: &Scripts) -> String {
+    let mut hasher = Sha256::new();
+    for (path, contents) in scripts {
+        let path = path
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        hasher.update(path.as_bytes());
+        hasher.update([0]);
+        hasher.update((contents.len() as u64).to_le_bytes());
+        hasher.update(contents);
+    }
+    let digest = hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect::<String>();
+    format!("sha256:{digest}")
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// The dependency `shulker add` adds for a path, named after the file or directory unless a name is given.
+/// Paths ending in `.tar`, `.tar.gz` or `.tgz` are tarballs, anything else is a project directory.
+pub fn dependency(source: &str, name: Option<&str>) -> (String, Dependency) {
+    let path = PathBuf::from(source);
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let stem = [".tar.gz", ".tgz", ".tar"]
+        .iter()
+        .find_map(|extension| file_name.strip_suffix(extension));
+    let name = match name {
+        Some(name) => name.to_string(),
+        None => stem
+            .unwrap_or(&file_name)
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect(),
+    };
+    let dependency = match stem {
+        Some(_) => Dependency::Tarball { tarball: path },
+        None => Dependency::Path { path },
+    };
+    (name, dependency)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_dir::TestDir;
+
+    #[test]
+    fn path_dependencies_are_locked() {
+        let dir = TestDir::new("path-dep");
+        std::fs::create_dir_all(dir.join("lib/scripts")).unwrap();
+        std::fs::write(dir.join("lib/blackstone.toml"), "[plot]\nname = \"lib\"").unwrap();
+        std::fs::write(dir.join("lib/scripts/spawn.bls"), "func spawn() {}").unwrap();
+
+        let mut manifest = Manifest::default();
+        let (name, dependency) = dependency("lib", None);
+        manifest.dependencies.insert(name, dependency);
+        let packages = install(&dir, &manifest, &[]).unwrap();
+        assert_eq!(packages[0].root, dir.join("deps/lib"));
+        assert!(dir.join("deps/lib/spawn.bls").is_file());
+        let lock = Lockfile::read(&dir.join(LOCK_FILE)).unwrap();
+        assert_eq!(lock.packages[0].source, "path+lib");
+        assert!(lock.packages[0].hash.starts_with("sha256:"));
+
+        // Unchanged scripts aren't copied again.
+        std::fs::write(dir.join("deps/lib/marker"), "").unwrap();
+        install(&dir, &manifest, &[]).unwrap();
+        assert!(dir.join("deps/lib/marker").is_file());
+
+        // A directory that changed is copied and locked again.
+        std::fs::write(dir.join("lib/scripts/spawn.bls"), "func spawn2() {}").unwrap();
+        install(&dir, &manifest, &[]).unwrap();
+        assert!(!dir.join("deps/lib/marker").exists());
+        assert_eq!(
+            std::fs::read_to_string(dir.join("deps/lib/spawn.bls")).unwrap(),
+            "func spawn2() {}"
+        );
+        assert_ne!(Lockfile::read(&dir.join(LOCK_FILE)).unwrap(), lock);
+    }
+
+    /// A tarball holding the given files, whose paths are written as they are.
+    fn tarball(files: &[(&str, &str)]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(vec![]);
+        for (path, contents) in files {
+            let mut header = tar::Header::new_gnu();
+            // `set_path` refuses paths with `..`, which are exactly what has to be tested.
+            header.as_old_mut().name[..path.len()].copy_from_slice(path.as_bytes());
+            header.set_size(contents.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append(&header, contents.as_bytes()).unwrap();
+        }
+        builder.into_inner().unwrap()
+    }
+
+    #[test]
+    fn tarballs_are_read_from_their_prefix() {
+        let dir = TestDir::new("tarball-dep");
+        let files = [
+            (
+                "entity_utils/blackstone.toml",
+                "[plot]\nname = \"x\"\n[build]\nsource = \"src\"",
+            ),
+            ("entity_utils/src/spawn.bls", "func spawn() {}"),
+            ("entity_utils/README.md", "# entity utils"),
+        ];
+        std::fs::write(dir.join("entity_utils.tar"), tarball(&files)).unwrap();
+
+        let (name, dependency) = dependency("entity_utils.tar", None);
+        assert_eq!(name, "entity_utils");
+        let scripts = fetch(&dir, &dependency).unwrap();
+        assert_eq!(
+            scripts.keys().collect::<Vec<_>>(),
+            vec![Path::new("spawn.bls")]
+        );
+    }
+
+    #[test]
+    fn changed_tarballs_need_updating() {
+        let dir = TestDir::new("tarball-changed");
+        std::fs::write(
+            dir.join("lib.tar"),
+            tarball(&[("spawn.bls", "func spawn() {}")]),
+        )
+        .unwrap();
+        let mut manifest = Manifest::default();
+        let (name, dependency) = dependency("lib.tar", None);
+        manifest.dependencies.insert(name, dependency);
+        install(&dir, &manifest, &[]).unwrap();
+
+        std::fs::write(
+            dir.join("lib.tar"),
+            tarball(&[("spawn.bls", "func spawn2() {}")]),
+        )
+        .unwrap();
+        let error = install(&dir, &manifest, &[]).unwrap_err();
+        assert!(error
+            .to_string()
+            .starts_with("The scripts of `lib` changed"));
+        install(&dir, &manifest, &["lib"]).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(dir.join("deps/lib/spawn.bls")).unwrap(),
+            "func spawn2() {}"
+        );
+    }
+
+    #[test]
+    fn tarball_paths_stay_inside_it() {
+        let dir = TestDir::new("tarball-escape");
+        std::fs::write(
+            dir.join("lib.tar"),
+            tarball(&[("./lib/spawn.bls", "func spawn() {}")]),
+        )
+        .unwrap();
+        let (_, dependency) = dependency("lib.tar", None);
+        assert_eq!(
+            fetch(&dir, &dependency).unwrap().keys().collect::<Vec<_>>(),
+            vec![Path::new("spawn.bls")]
+        );
+
+        for escape in ["lib/../../x.bls", "/x.bls"] {
+            std::fs::write(dir.join("lib.tar"), tarball(&[(escape, "func x() {}")])).unwrap();
+            let error = fetch(&dir, &dependency).unwrap_err();
+            assert!(
+                error
+                    .to_string()
+                    .ends_with(&format!("has a file outside of it, `{escape}`")),
+                "{error}"
+            );
+        }
+    }
+
+    #[test]
+    fn tarballs_without_a_prefix() {
+        let dir = TestDir::new("tarball-flat");
+        std::fs::write(
+            dir.join("lib.tar"),
+            tarball(&[("spawn.bls", "func spawn() {}")]),
+        )
+        .unwrap();
+        let (_, dependency) = dependency("lib.tar", None);
+        assert_eq!(
+            fetch(&dir, &dependency).unwrap().keys().collect::<Vec<_>>(),
+            vec![Path::new("spawn.bls")]
+        );
+    }
+}
@@ -1,25 +1,31 @@
-use super::ident;
+use super::{ident, padding};
 use chumsky::extra::Err;
 use chumsky::{
+    error::RichReason,
     prelude::Rich,
-    primitive::{choice, just, none_of},
-    text, IterParser, Parser,
+    primitive::{any, choice, just, none_of, one_of},
+    text, ConfigIterParser, IterParser, Parser,
 };
 
-use crate::codegen::item_data::ItemData;
+use chumsky::{input::Emitter, span::SimpleSpan};
+
+use crate::codegen::{
+    action_dump::{simplify_name, Particle, ACTION_DUMP},
+    item_data::ItemData,
+    misc::GameValueTarget,
+    template::{ParticleData, ParticleMotion},
+};
 
 use super::ident_to_var;
 
+/// The names that start a literal value instead of calling an action, e.g. `vec(1, 2, 3)`.
+pub const LITERALS: [&str; 7] = ["item", "items", "loc", "sound", "vec", "potion", "particle"];
+
 pub fn parse_number<'a>() -> impl Parser<'a, &'a str, ItemData, Err<Rich<'a, char>>> {
     // Number
     // This argument represents a Number type on DiamondFire.
     // It is parsed from an integer literal to a Number.
-    let number = text::int(10)
-        .then(just('.').then(text::digits(10)).or_not())
-        .slice()
-        .from_str()
-        .unwrapped()
-        .map(|f| ItemData::Number { data: f });
+    let number = float().map(|f| ItemData::Number { data: f });
     number
 }
 
@@ -27,55 +33,174 @@ pub fn parse_text<'a>() -> impl Parser<'a, &'a str, ItemData, Err<Rich<'a, char>
     // Text
     // This argument represents a Text type on DiamondFire.
     // It is converted from a String literal.
-    just('"')
-        .ignore_then(none_of('"').repeated().collect::<String>())
-        .then_ignore(just('"'))
-        .map(|f| ItemData::Text { data: f })
+    text_literal().map(|f| ItemData::Text { data: f })
 }
 
-pub fn parse_location<'a>() -> impl Parser<'a, &'a str, ItemData, Err<Rich<'a, char>>> {
-    let location = text::keyword("loc")
-        .ignore_then(parse_number().repeated().collect::<Vec<ItemData>>())
-        .try_map(|f: Vec<ItemData>, f2| {
-            if f.len() == 3 {
-                if let Some(ItemData::Number { data: n1 }) = f.get(0) {
-                    if let Some(ItemData::Number { data: n2 }) = f.get(1) {
-                        if let Some(ItemData::Number { data: n3 }) = f.get(2) {
-                            return Ok(ItemData::Location {
-                                x: *n1,
-                                y: *n2,
-                                z: *n3,
-                                pitch: 0.0,
-                                yaw: 0.0,
-                            });
-                        }
-                    }
+pub fn parse_sound<'a>() -> impl Parser<'a, &'a str, ItemData, Err<Rich<'a, char>>> {
+    // Sound
+    // This argument represents a Sound type on DiamondFire.
+    // It is written as `sound("Name", pitch, volume)`. The pitch and volume can be left out.
+    text::keyword("sound")
+        .ignore_then(
+            string()
+                .map_with_span(|name, span| (name, span))
+                .then(comma().ignore_then(float()).or_not())
+                .then(comma().ignore_then(float()).or_not())
+                .padded_by(padding())
+                .delimited_by(just('('), just(')')),
+        )
+        .validate(|(((name, span), pitch), vol), _, emitter| {
+            let sound = match ACTION_DUMP.find_sound(&name) {
+                Ok(sound) => sound.icon.name.clone(),
+                Err(suggestion) => {
+                    emitter.emit(unknown_name("sound", &name, suggestion, span));
+                    name
                 }
-            } else if f.len() == 5 {
-                if let Some(ItemData::Number { data: n1 }) = f.get(0) {
-                    if let Some(ItemData::Number { data: n2 }) = f.get(1) {
-                        if let Some(ItemData::Number { data: n3 }) = f.get(2) {
-                            if let Some(ItemData::Number { data: n4 }) = f.get(3) {
-                                if let Some(ItemData::Number { data: n5 }) = f.get(4) {
-                                    return Ok(ItemData::Location {
-                                        x: *n1,
-                                        y: *n2,
-                                        z: *n3,
-                                        pitch: *n4,
-                                        yaw: *n5,
-                                    });
-                                }
-                            }
-                        }
-                    }
+            };
+            ItemData::Sound {
+                sound,
+                pitch: pitch.unwrap_or(1.0),
+                vol: vol.unwrap_or(2.0),
+            }
+        })
+}
+
+pub fn parse_vector<'a>() -> impl Parser<'a, &'a str, ItemData, Err<Rich<'a, char>>> {
+    // Vector
+    // This argument represents a Vector type on DiamondFire.
+    // It is written as `vec(x, y, z)`.
+    text::keyword("vec")
+        .ignore_then(
+            float()
+                .padded_by(padding())
+                .then(comma().ignore_then(float()))
+                .then(comma().ignore_then(float()))
+                .padded_by(padding())
+                .delimited_by(just('('), just(')')),
+        )
+        .map(|((x, y), z)| ItemData::Vector { x, y, z })
+}
+
+pub fn parse_potion<'a>() -> impl Parser<'a, &'a str, ItemData, Err<Rich<'a, char>>> {
+    // Potion
+    // This argument represents a Potion type on DiamondFire.
+    // It is written as `potion("Name", duration, amplifier)`, with the duration in ticks.
+    // The duration (infinite by default) and amplifier can be left out.
+    let whole = comma().ignore_then(float().map_with_span(|n, span| (n, span)));
+    text::keyword("potion")
+        .ignore_then(
+            string()
+                .map_with_span(|name, span| (name, span))
+                .then(whole.clone().or_not())
+                .then(whole.or_not())
+                .padded_by(padding())
+                .delimited_by(just('('), just(')')),
+        )
+        .validate(|(((name, span), dur), amp), _, emitter| {
+            let effect = match ACTION_DUMP.find_potion(&name) {
+                Ok(potion) => potion.icon.name.clone(),
+                Err(suggestion) => {
+                    emitter.emit(unknown_name("potion", &name, suggestion, span));
+                    name
                 }
+            };
+            ItemData::Potion {
+                effect,
+                dur: dur.map_or(1000000, |(n, span)| whole_number(n, span, emitter)),
+                amp: amp.map_or(0, |(n, span)| whole_number(n, span, emitter)),
             }
+        })
+}
 
-            // Report::build(ReportKind::Warning, (), 5);
-            // TODO: throw ariadne error
-            return Err(Rich::custom(f2, "Locations must have 3 or 5 numbers."));
-        });
-    location
+pub fn parse_particle<'a>() -> impl Parser<'a, &'a str, ItemData, Err<Rich<'a, char>>> {
+    // Particle
+    // This argument represents a Particle type on DiamondFire.
+    // It is written as `particle("Name", amount, horizontal, vertical; field = value, ...)`.
+    // Everything after the name can be left out, and the fields depend on the particle (e.g. `size` or `motion`).
+    let field = text::ident()
+        .map_with_span(|name: &str, span| (name.to_string(), span))
+        .then_ignore(just('=').padded_by(padding()))
+        .then(choice((parse_vector(), parse_text(), parse_number())));
+
+    text::keyword("particle")
+        .ignore_then(
+            string()
+                .map_with_span(|name, span| (name, span))
+                .then(
+                    comma()
+                        .ignore_then(float().map_with_span(|n, span| (n, span)))
+                        .or_not(),
+                )
+                .then(
+                    comma()
+                        .ignore_then(float())
+                        .then(comma().ignore_then(float()))
+                        .or_not(),
+                )
+                .then(
+                    just(';')
+                        .ignore_then(
+                            field
+                                .padded_by(padding())
+                                .separated_by(just(','))
+                                .collect::<Vec<_>>(),
+                        )
+                        .or_not(),
+                )
+                .padded_by(padding())
+                .delimited_by(just('('), just(')')),
+        )
+        .validate(|((((name, span), amount), spread), fields), _, emitter| {
+            let amount = amount.map_or(1, |(n, span)| whole_number(n, span, emitter));
+            let fields = fields.unwrap_or_default();
+            let (name, data) = match ACTION_DUMP.find_particle(&name) {
+                Ok(particle) => (
+                    particle.icon.name.clone(),
+                    particle_data(particle, fields, emitter),
+                ),
+                Err(suggestion) => {
+                    emitter.emit(unknown_name("particle", &name, suggestion, span));
+                    (name, ParticleData::default())
+                }
+            };
+            ItemData::Particle {
+                name,
+                amount,
+                spread: spread.unwrap_or((0.0, 0.0)),
+                data,
+            }
+        })
+}
+
+pub fn parse_location<'a>() -> impl Parser<'a, &'a str, ItemData, Err<Rich<'a, char>>> {
+    // Location
+    // This argument represents a Location type on DiamondFire.
+    // It is written as `loc(x, y, z)`, or `loc(x, y, z, pitch, yaw)` to give it a direction.
+    text::keyword("loc")
+        .ignore_then(
+            float()
+                .padded_by(padding())
+                .separated_by(just(','))
+                .collect::<Vec<f32>>()
+                .delimited_by(just('('), just(')')),
+        )
+        .validate(|numbers, span, emitter| {
+            let (x, y, z, pitch, yaw) = match numbers[..] {
+                [x, y, z] => (x, y, z, 0.0, 0.0),
+                [x, y, z, pitch, yaw] => (x, y, z, pitch, yaw),
+                _ => {
+                    emitter.emit(Rich::custom(span, "Locations must have 3 or 5 numbers."));
+                    (0.0, 0.0, 0.0, 0.0, 0.0)
+                }
+            };
+            ItemData::Location {
+                x,
+                y,
+                z,
+                pitch,
+                yaw,
+            }
+        })
 }
 
 pub fn variable_parser<'a>() -> impl Parser<'a, &'a str, ItemData, Err<Rich<'a, char>>> {
@@ -85,44 +210,336 @@ pub fn variable_parser<'a>() -> impl Parser<'a, &'a str, ItemData, Err<Rich<'a,
 
 pub fn parse_item_stack<'a>() -> impl Parser<'a, &'a str, ItemData, Err<Rich<'a, char>>> {
     let item = text::keyword("item")
-        .ignore_then(parse_text().padded().delimited_by(just('('), just(')')))
+        .ignore_then(
+            parse_text()
+                .padded_by(padding())
+                .delimited_by(just('('), just(')')),
+        )
         .try_map(|f, span| {
             if let ItemData::Text { data } = f {
                 return Ok(ItemData::VanillaItem {
-                    data: format!("{{Count:1b,DF_NBT:3337,id:\\\"minecraft:{data}\\\"}}"),
+                    data: format!("{{Count:1b,DF_NBT:3337,id:\"minecraft:{data}\"}}"),
                 });
             }
-            return Err(Rich::custom(span, "Failed to provide valid item."));
+            Err(Rich::custom(span, "Failed to provide valid item."))
         });
 
     let item_stack = text::keyword("items")
         .ignore_then(
             ident()
-                .padded()
+                .padded_by(padding())
                 .then_ignore(just(';'))
-                .padded()
+                .padded_by(padding())
                 .then(parse_number())
-                .padded()
+                .padded_by(padding())
                 .delimited_by(just('('), just(')'))
         )
         .try_map(|(id, num), f2| {
             if let ItemData::Number { data } = num {
                 return Ok(ItemData::VanillaItem {
-                    data: format!("{{Count:{data}b,DF_NBT:3337,id:\\\"minecraft:{id}\\\"}}"),
+                    data: format!("{{Count:{data}b,DF_NBT:3337,id:\"minecraft:{id}\"}}"),
                 });
             }
-            return Err(Rich::custom(f2, "Somehow reached an invalid position. If you get this error, please reach out to us."));
+            Err(Rich::custom(f2, "Somehow reached an invalid position. If you get this error, please reach out to us."))
         });
 
     item.or(item_stack).boxed()
 }
 
+pub fn parse_game_value<'a>() -> impl Parser<'a, &'a str, ItemData, Err<Rich<'a, char>>> {
+    // Game Value
+    // This argument represents a Game Value on DiamondFire.
+    // It is written as `target.Name`, e.g. `value.EventBlockLocation` or `killer.Location`.
+    // `game.` is the prefix of unsaved variables, so game values of the default target use `value.` instead.
+    let target = choice((
+        text::keyword("value").to(GameValueTarget::Default),
+        text::keyword("default").to(GameValueTarget::Default),
+        text::keyword("selection").to(GameValueTarget::Selection),
+        text::keyword("killer").to(GameValueTarget::Killer),
+        text::keyword("damager").to(GameValueTarget::Damager),
+        text::keyword("victim").to(GameValueTarget::Victim),
+        text::keyword("shooter").to(GameValueTarget::Shooter),
+        text::keyword("projectile").to(GameValueTarget::Projectile),
+        text::keyword("lastEntity").to(GameValueTarget::LastEntity),
+    ));
+
+    let targeted = target
+        .then_ignore(just('.'))
+        .then(text::ident().map_with_span(|name: &str, span| (name, span)))
+        .validate(
+            |(target, (name, span)), _, emitter| match ACTION_DUMP.find_game_value(name) {
+                Some(value) => ItemData::GameValue {
+                    kind: value.icon.name.clone(),
+                    target,
+                },
+                None => {
+                    emitter.emit(Rich::custom(span, format!("Unknown game value `{name}`")));
+                    ItemData::GameValue {
+                        kind: name.to_string(),
+                        target,
+                    }
+                }
+            },
+        );
+
+    targeted.boxed()
+}
+
 pub fn arguments_parser<'a>() -> impl Parser<'a, &'a str, ItemData, Err<Rich<'a, char>>> {
     choice((
         parse_text(),
         parse_number(),
         parse_item_stack(),
         parse_location(),
+        parse_sound(),
+        parse_vector(),
+        parse_potion(),
+        parse_particle(),
+        parse_game_value(),
         variable_parser(),
     ))
 }
+
+/// A number literal, e.g. `5`, `-2` or `0.5`.
+fn float<'a>() -> impl Parser<'a, &'a str, f32, Err<Rich<'a, char>>> + Clone {
+    just('-')
+        .or_not()
+        .then(text::int(10))
+        .then(just('.').then(text::digits(10)).or_not())
+        .slice()
+        .from_str()
+        .unwrapped()
+}
+
+/// A string literal, e.g. `"hi"`. Outputs the contents without the quotes.
+/// Supports the escapes `\"`, `\\`, `\n`, `\t`, `\{`, `\}` and `\u{XXXX}`, and can't span several lines.
+/// `&` colour codes are left as they are, since DF formats them itself.
+/// Raw strings like `r"C:\path"` or `r#"say "hi""#` have no escapes and can span several lines.
+fn string<'a>() -> impl Parser<'a, &'a str, String, Err<Rich<'a, char>>> + Clone {
+    quoted(false)
+}
+
+/// A text literal, a string that can also contain variables, e.g. `"Hello {local.name}!"`.
+/// These become DF's `%var(name)` placeholders.
+fn text_literal<'a>() -> impl Parser<'a, &'a str, String, Err<Rich<'a, char>>> + Clone {
+    quoted(true)
+}
+
+/// A plain or raw string literal, turning `{variable}`s into placeholders if `interpolate` is set.
+fn quoted<'a>(interpolate: bool) -> impl Parser<'a, &'a str, String, Err<Rich<'a, char>>> + Clone {
+    let escape = just('\\').ignore_then(choice((
+        one_of("\"\\{}"),
+        just('n').to('\n'),
+        just('t').to('\t'),
+        just('u')
+            .ignore_then(text::digits(16).slice().delimited_by(just('{'), just('}')))
+            .validate(|code: &str, span, emitter| {
+                u32::from_str_radix(code, 16)
+                    .ok()
+                    .and_then(char::from_u32)
+                    .unwrap_or_else(|| {
+                        emitter.emit(Rich::custom(
+                            span,
+                            format!("`{code}` isn't a valid character code"),
+                        ));
+                        char::REPLACEMENT_CHARACTER
+                    })
+            }),
+        none_of('\n').validate(|c, span, emitter| {
+            emitter.emit(Rich::custom(
+                span,
+                format!("Unknown escape sequence `\\{c}`"),
+            ));
+            c
+        }),
+    )));
+
+    // DF looks a `%var` placeholder up by name alone, so the scope is only there for readability.
+    let variable = just('{')
+        .filter(move |_| interpolate)
+        .ignore_then(ident().padded().then_ignore(just('}')).map_err(|error| {
+            Rich::custom(
+                *error.span(),
+                "Expected a variable followed by `}`, write `\\{` for a literal `{`",
+            )
+        }))
+        .map(|name| {
+            let name = name.split_once('.').map_or(name.as_str(), |(_, name)| name);
+            format!("%var({name})")
+        });
+
+    let plain = just('"').ignore_then(
+        choice((
+            escape.map(String::from),
+            variable,
+            none_of(if interpolate { "\"\\\n{" } else { "\"\\\n" }).map(String::from),
+        ))
+        .repeated()
+        .collect::<Vec<_>>()
+        .map(|pieces| pieces.concat())
+        .then_ignore(just('"'))
+        .map_err_with_state(|error, span, _| unterminated(error, span)),
+    );
+
+    // The closing quote of a raw string has to be followed by as many `#`s as the opening one.
+    let raw = just('r')
+        .ignore_then(just('#').repeated().count())
+        .then_ignore(just('"'))
+        .then_with_ctx({
+            let end = just('"').then(
+                just('#')
+                    .repeated()
+                    .configure(|cfg, hashes: &usize| cfg.exactly(*hashes)),
+            );
+            any()
+                .and_is(end.not())
+                .repeated()
+                .collect::<String>()
+                .then_ignore(end)
+                .map_err_with_state(|error, span, _| unterminated(error, span))
+        });
+
+    choice((raw, plain))
+}
+
+/// Replaces the error of a string that reached the end of its line (or of the file) with one pointing at its opening quote.
+/// The span is that of the contents, starting right after the quote.
+fn unterminated<'a>(error: Rich<'a, char>, span: SimpleSpan) -> Rich<'a, char> {
+    let custom = matches!(error.reason(), RichReason::Custom(_));
+    if !custom && matches!(error.found(), None | Some('\n')) {
+        Rich::custom(
+            SimpleSpan::new(span.start - 1, span.start),
+            "Unterminated string, this quote is never closed",
+        )
+    } else {
+        error
+    }
+}
+
+/// The `,` between the values of a literal.
+fn comma<'a>() -> impl Parser<'a, &'a str, char, Err<Rich<'a, char>>> + Clone {
+    just(',').padded_by(padding())
+}
+
+/// Converts a number that has to be a whole number, like a potion duration.
+fn whole_number(n: f32, span: SimpleSpan, emitter: &mut Emitter<Rich<char>>) -> u32 {
+    if n < 0.0 || n.fract() != 0.0 {
+        emitter.emit(Rich::custom(
+            span,
+            format!("Expected a whole number of at least 0, found `{n}`"),
+        ));
+        return 0;
+    }
+    n as u32
+}
+
+/// The error for a sound, potion or particle name that isn't in the action dump.
+fn unknown_name<'a>(
+    kind: &str,
+    name: &str,
+    suggestion: Option<&str>,
+    span: SimpleSpan,
+) -> Rich<'a, char> {
+    let mut message = format!("Unknown {kind} `{name}`");
+    if let Some(suggestion) = suggestion {
+        message.push_str(&format!(", did you mean `{suggestion}`?"));
+    }
+    Rich::custom(span, message)
+}
+
+/// Fills in the extra fields of a particle. Fields that aren't given get DF's default value.
+fn particle_data(
+    particle: &Particle,
+    fields: Vec<((String, SimpleSpan), ItemData)>,
+    emitter: &mut Emitter<Rich<char>>,
+) -> ParticleData {
+    let mut data = ParticleData::default();
+    for field in &particle.fields {
+        match field.as_str() {
+            "Motion" => {
+                data.motion = Some(ParticleMotion {
+                    x: 0.0,
+                    y: 0.0,
+                    z: 0.0,
+                })
+            }
+            "Motion Variation" => data.motion_variation = Some(100),
+            "Color" => data.rgb = Some(0xFF0000),
+            "Color Variation" => data.color_variation = Some(0),
+            "Size" => data.size = Some(1.0),
+            "Size Variation" => data.size_variation = Some(0),
+            "Material" => data.material = Some("STONE".to_string()),
+            _ => {}
+        }
+    }
+
+    for ((name, span), value) in fields {
+        let Some(field) = particle
+            .fields
+            .iter()
+            .find(|field| simplify_name(field) == simplify_name(&name))
+        else {
+            let fields = if particle.fields.is_empty() {
+                "none".to_string()
+            } else {
+                particle.fields.join(", ")
+            };
+            emitter.emit(Rich::custom(
+                span,
+                format!(
+                    "`{}` has no field `{name}`, its fields are: {fields}",
+                    particle.icon.name
+                ),
+            ));
+            continue;
+        };
+        let wrong_type = |expected: &str| {
+            Rich::custom(
+                span,
+                format!("`{field}` should be {expected}, found {}", value.describe()),
+            )
+        };
+        match (field.as_str(), &value) {
+            ("Motion", ItemData::Vector { x, y, z }) => {
+                data.motion = Some(ParticleMotion {
+                    x: *x,
+                    y: *y,
+                    z: *z,
+                })
+            }
+            ("Motion", _) => emitter.emit(wrong_type("a Vector")),
+            ("Color", ItemData::Text { data: hex }) => {
+                match hex
+                    .strip_prefix('#')
+                    .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                {
+                    Some(rgb) => data.rgb = Some(rgb),
+                    None => emitter.emit(Rich::custom(
+                        span,
+                        format!("`{hex}` isn't a color, expected something like \"#FF0000\""),
+                    )),
+                }
+            }
+            ("Color", ItemData::Number { data: rgb }) => {
+                data.rgb = Some(whole_number(*rgb, span, emitter))
+            }
+            ("Color", _) => emitter.emit(wrong_type("a Number or a Text like \"#FF0000\"")),
+            ("Material", ItemData::Text { data: material }) => {
+                data.material = Some(material.to_uppercase().replace(' ', "_"))
+            }
+            ("Material", _) => emitter.emit(wrong_type("a Text")),
+            ("Size", ItemData::Number { data: size }) => data.size = Some(*size),
+            (variation, ItemData::Number { data: n }) => {
+                let n = whole_number(*n, span, emitter);
+                match variation {
+                    "Motion Variation" => data.motion_variation = Some(n),
+                    "Color Variation" => data.color_variation = Some(n),
+                    "Size Variation" => data.size_variation = Some(n),
+                    _ => {}
+                }
+            }
+            _ => emitter.emit(wrong_type("a Number")),
+        }
+    }
+    data
+}
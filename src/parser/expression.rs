@@ -0,0 +1,320 @@
+use chumsky::{
+    extra::Err,
+    input::Emitter,
+    prelude::Rich,
+    primitive::{choice, just},
+    recursive::recursive,
+    span::SimpleSpan,
+    IterParser, Parser,
+};
+
+use crate::codegen::{block::Block, item::Item, item_data::ItemData, misc::VariableScope};
+
+use super::{datatypes::arguments_parser, padding, parse::add_tags};
+
+/// An arithmetic expression, e.g. `local.hp * 2 + 5`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expression {
+    /// A single argument, e.g. a number or a variable.
+    Value(ItemData, SimpleSpan),
+    /// A `-` in front of an expression.
+    Negate(Box<Expression>),
+    /// Two expressions joined by an operator.
+    Binary(Operator, Box<Expression>, Box<Expression>),
+}
+
+/// An arithmetic operator, each of which is a Set Variable action.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Remainder,
+}
+
+impl Operator {
+    /// The Set Variable action that applies this operator.
+    fn action(self) -> &'static str {
+        match self {
+            Self::Add => "+",
+            Self::Subtract => "-",
+            Self::Multiply => "x",
+            Self::Divide => "/",
+            Self::Remainder => "%",
+        }
+    }
+
+    /// Whether `(a op b) op c` can be done by one block taking `a, b, c`.
+    /// Subtract and Divide apply every other value to the first one, so this works for them too.
+    fn chains(self) -> bool {
+        self != Self::Remainder
+    }
+
+    /// Whether `a op (b op c)` can also be done by one block taking `a, b, c`.
+    fn associative(self) -> bool {
+        matches!(self, Self::Add | Self::Multiply)
+    }
+}
+
+/// Parses an arithmetic expression with the usual precedence: `*`, `/` and `%` before `+` and `-`.
+/// Parentheses group, and `-` in front of a value negates it.
+pub fn expression<'a>() -> impl Parser<'a, &'a str, Expression, Err<Rich<'a, char>>> {
+    recursive(|expression| {
+        let atom = choice((
+            arguments_parser().map_with_span(Expression::Value),
+            expression
+                .padded_by(padding())
+                .delimited_by(just('('), just(')')),
+        ))
+        .padded_by(padding());
+
+        let unary = just('-')
+            .padded_by(padding())
+            .repeated()
+            .foldr(atom, |_, value| Expression::Negate(Box::new(value)))
+            .boxed();
+
+        let product = unary
+            .clone()
+            .foldl(
+                choice((
+                    just('*').to(Operator::Multiply),
+                    just('/').to(Operator::Divide),
+                    just('%').to(Operator::Remainder),
+                ))
+                .then(unary)
+                .repeated(),
+                |left, (op, right)| Expression::Binary(op, Box::new(left), Box::new(right)),
+            )
+            .boxed();
+
+        product.clone().foldl(
+            choice((
+                just('+').to(Operator::Add),
+                just('-').to(Operator::Subtract),
+            ))
+            .then(product)
+            .repeated(),
+            |left, (op, right)| Expression::Binary(op, Box::new(left), Box::new(right)),
+        )
+    })
+}
+
+/// Lowers `var = expression` into Set Variable blocks.
+/// Parts of the expression that need their own block are stored in local temporaries named `__tmp0`, `__tmp1`, ...
+/// Identifiers can't contain `_`, so these never collide with variables in the script.
+pub fn lower_assignment<'a>(
+    var: ItemData,
+    expression: &Expression,
+    emitter: &mut Emitter<Rich<char>>,
+) -> Vec<Option<Block<'a>>> {
+    let mut lowering = Lowering {
+        blocks: vec![],
+        temporaries: 0,
+        emitter,
+    };
+    let result = lowering.lower(expression, Some(&var));
+    if result != var {
+        lowering.emit("=", var, vec![result]);
+    }
+    lowering.blocks
+}
+
+struct Lowering<'a, 'e, 'b> {
+    blocks: Vec<Option<Block<'a>>>,
+    /// How many temporaries are in use right now.
+    temporaries: usize,
+    emitter: &'e mut Emitter<Rich<'b, char>>,
+}
+
+impl<'a> Lowering<'a, '_, '_> {
+    /// Emits the blocks computing an expression, storing it in `into` if it needs a block.
+    /// Returns where the value ends up, which is the value itself if it didn't need a block.
+    fn lower(&mut self, expression: &Expression, into: Option<&ItemData>) -> ItemData {
+        match expression {
+            Expression::Value(data, _) => data.clone(),
+            Expression::Negate(inner) => {
+                if let Expression::Value(ItemData::Number { data }, _) = inner.as_ref() {
+                    return ItemData::Number { data: -data };
+                }
+                let base = self.temporaries;
+                let value = self.operand(inner, None);
+                self.temporaries = base;
+                let result = self.result(into);
+                self.emit(
+                    "x",
+                    result.clone(),
+                    vec![value, ItemData::Number { data: -1.0 }],
+                );
+                result
+            }
+            Expression::Binary(op, ..) => {
+                let mut operands = vec![];
+                flatten(*op, expression, &mut operands);
+                let base = self.temporaries;
+                let mut values = vec![];
+                for (index, operand) in operands.iter().enumerate() {
+                    // The first operand can be computed straight into the result,
+                    // as long as nothing after it still needs the old value.
+                    let target = into.filter(|var| {
+                        index == 0 && !operands[1..].iter().any(|other| reads(other, var))
+                    });
+                    values.push(self.operand(operand, target));
+                }
+                self.temporaries = base;
+                let result = self.result(into);
+                self.emit(op.action(), result.clone(), values);
+                result
+            }
+        }
+    }
+
+    /// Lowers an operand of an operator, which has to be a number.
+    fn operand(&mut self, expression: &Expression, into: Option<&ItemData>) -> ItemData {
+        if let Expression::Value(data, span) = expression {
+            if !matches!(
+                data,
+                ItemData::Number { .. } | ItemData::Variable { .. } | ItemData::GameValue { .. }
+            ) {
+                self.emitter.emit(Rich::custom(
+                    *span,
+                    format!(
+                        "Arithmetic only works on numbers, found {}",
+                        data.describe()
+                    ),
+                ));
+            }
+        }
+        self.lower(expression, into)
+    }
+
+    /// The variable a result is stored in: the given one, or a new temporary.
+    fn result(&mut self, into: Option<&ItemData>) -> ItemData {
+        if let Some(var) = into {
+            return var.clone();
+        }
+        let var = ItemData::Variable {
+            scope: VariableScope::Local,
+            name: format!("__tmp{}", self.temporaries),
+        };
+        self.temporaries += 1;
+        var
+    }
+
+    fn emit(&mut self, action: &str, var: ItemData, values: Vec<ItemData>) {
+        let mut items = vec![];
+        for (slot, data) in std::iter::once(var).chain(values).enumerate() {
+            items.push(Item {
+                id: data.repr(),
+                slot: slot.try_into().expect("failed to convert to usize"),
+                item: data,
+            })
+        }
+        add_tags("set_var", action, &mut items, vec![], self.emitter);
+        self.blocks.push(Some(Block::Code {
+            block: "set_var",
+            items,
+            action: action.to_string(),
+            data: "",
+            target: "",
+            inverted: "",
+            sub_action: String::new(),
+        }));
+    }
+}
+
+/// Collects the operands of a chain of the same operator that one block can take at once.
+fn flatten<'e>(op: Operator, expression: &'e Expression, out: &mut Vec<&'e Expression>) {
+    match expression {
+        Expression::Binary(inner, left, right) if *inner == op => {
+            if op.chains() {
+                flatten(op, left, out);
+            } else {
+                out.push(left);
+            }
+            if op.associative() {
+                flatten(op, right, out);
+            } else {
+                out.push(right);
+            }
+        }
+        _ => out.push(expression),
+    }
+}
+
+/// Whether an expression uses the given variable.
+fn reads(expression: &Expression, var: &ItemData) -> bool {
+    match expression {
+        Expression::Value(data, _) => data == var,
+        Expression::Negate(inner) => reads(inner, var),
+        Expression::Binary(_, left, right) => reads(left, var) || reads(right, var),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chumsky::Parser;
+
+    use crate::{codegen::block::Block, parser::parse::actions_parser};
+
+    /// Compiles a statement and writes each Set Variable block as `action var = values`, leaving out tags.
+    fn lowered(statement: &str) -> Vec<String> {
+        let blocks = actions_parser()
+            .parse(statement)
+            .into_result()
+            .expect("failed to parse");
+        blocks
+            .into_iter()
+            .flatten()
+            .map(|block| {
+                let Block::Code { items, action, .. } = block else {
+                    panic!("expected a code block");
+                };
+                let values = items
+                    .iter()
+                    .filter(|item| item.id != "bl_tag")
+                    .map(|item| match item.item.to_template() {
+                        Some(crate::codegen::template::TemplateItem::Variable { name, .. }) => name,
+                        Some(crate::codegen::template::TemplateItem::Number { name }) => name,
+                        other => format!("{other:?}"),
+                    })
+                    .collect::<Vec<_>>();
+                format!("{action} {} = {}", values[0], values[1..].join(", "))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn single_value_is_assigned() {
+        assert_eq!(lowered("var local.x = 5"), vec!["= x = 5"]);
+    }
+
+    #[test]
+    fn precedence_reuses_the_target() {
+        assert_eq!(
+            lowered("var local.hp = local.hp * 2 + 5"),
+            vec!["x hp = hp, 2", "+ hp = hp, 5"]
+        );
+    }
+
+    #[test]
+    fn chains_share_a_block() {
+        assert_eq!(
+            lowered("var local.x = local.a + local.b + 3 - (local.c - 1)"),
+            vec!["+ x = a, b, 3", "- __tmp0 = c, 1", "- x = x, __tmp0"]
+        );
+    }
+
+    #[test]
+    fn temporaries_are_used_when_the_target_is_read() {
+        assert_eq!(
+            lowered("var local.x = (local.a % 2) * -local.x"),
+            vec![
+                "% __tmp0 = a, 2",
+                "x __tmp1 = x, -1",
+                "x x = __tmp0, __tmp1"
+            ]
+        );
+    }
+}
@@ -1,15 +1,19 @@
 use chumsky::{
+    error::RichReason,
     prelude::Rich,
-    primitive::{one_of},
-    IterParser, Parser,
+    primitive::{any, choice, just, none_of, one_of},
+    recursive::recursive,
+    span::SimpleSpan,
+    Boxed, IterParser, Parser,
 };
 
 use crate::codegen::{item_data::ItemData, misc::VariableScope};
 use chumsky::extra::Err;
 pub mod datatypes;
+pub mod expression;
 pub mod parse;
 
-pub fn ident<'a>() -> impl Parser<'a, &'a str, String, Err<Rich<'a, char>>> {
+pub fn ident<'a>() -> impl Parser<'a, &'a str, String, Err<Rich<'a, char>>> + Clone {
     let pt2 = "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789%<>.";
     one_of("abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ%.")
         .then(one_of(pt2).repeated().collect::<String>())
@@ -20,9 +24,66 @@ pub fn ident<'a>() -> impl Parser<'a, &'a str, String, Err<Rich<'a, char>>> {
         })
 }
 
+/// Whitespace and comments between tokens.
+/// Use this with `.padded_by(padding())` instead of `.padded()`.
+pub fn padding<'a>() -> Boxed<'a, 'a, &'a str, (), Err<Rich<'a, char>>> {
+    comment().repeated().boxed()
+}
+
+/// Doc comments (`/// ...`) in front of a definition, along with any other padding.
+/// Outputs the lines of the doc comments, without the leading `///`.
+pub fn doc_comments<'a>() -> impl Parser<'a, &'a str, Vec<String>, Err<Rich<'a, char>>> {
+    let doc_comment = just("///")
+        .and_is(just("////").not())
+        .ignore_then(none_of('\n').repeated().slice())
+        .map(|line: &str| {
+            line.strip_prefix(' ')
+                .unwrap_or(line)
+                .trim_end()
+                .to_string()
+        });
+
+    choice((doc_comment.map(Some), comment().to(None)))
+        .repeated()
+        .collect::<Vec<_>>()
+        .map(|lines| lines.into_iter().flatten().collect())
+}
+
+/// A single piece of padding: a whitespace character, a `//` line comment or a (nestable) `/* */` block comment.
+fn comment<'a>() -> impl Parser<'a, &'a str, (), Err<Rich<'a, char>>> + Clone {
+    let line_comment = just("//").then(none_of('\n').repeated()).ignored();
+
+    let block_comment = recursive(|block_comment| {
+        just("/*").ignore_then(
+            choice((block_comment, any().and_is(just("*/").not()).ignored()))
+                .repeated()
+                .then(just("*/"))
+                .ignored()
+                // Only the end of the file can stop a comment, so the error points at where it was opened instead.
+                // A nested comment that isn't closed has already said so.
+                .map_err_with_state(|error: Rich<'a, char>, span: SimpleSpan, _| {
+                    if matches!(error.reason(), RichReason::Custom(_)) {
+                        error
+                    } else {
+                        Rich::custom(
+                            SimpleSpan::new(span.start - 2, span.start),
+                            "Unterminated comment, this `/*` is never closed",
+                        )
+                    }
+                }),
+        )
+    });
+
+    choice((
+        any().filter(|c: &char| c.is_whitespace()).ignored(),
+        line_comment,
+        block_comment,
+    ))
+}
+
 fn ident_to_var(input: &str) -> ItemData {
     let words = input.split('.').collect::<Vec<_>>();
-    if let Some(scope) = words.get(0) {
+    if let Some(scope) = words.first() {
         match *scope {
             "local" => ItemData::Variable {
                 scope: VariableScope::Local,
@@ -1,38 +1,72 @@
 use chumsky::{
     prelude::Rich,
-    primitive::{choice, just},
+    primitive::{any, choice, just, none_of},
+    recovery::via_parser,
     recursive::recursive,
     text, IterParser, Parser,
 };
 
 use crate::codegen::{
-    block::Block,
+    action_dump::{ArgumentError, ACTION_DUMP},
+    block::{Assertion, Block},
     item::Item,
     item_data::ItemData,
-    misc::{BracketDirection, BracketType},
+    misc::{BracketDirection, BracketType, VariableScope},
 };
-use chumsky::extra::Err;
+use chumsky::{extra::Err, input::Emitter, span::SimpleSpan};
 
 use super::{
-    datatypes::{arguments_parser, variable_parser},
-    ident,
+    datatypes::{arguments_parser, parse_text, variable_parser, LITERALS},
+    doc_comments,
+    expression::{expression, lower_assignment},
+    ident, padding,
 };
 
 pub fn parser<'a>() -> impl Parser<'a, &'a str, Vec<Vec<Option<Block<'a>>>>, Err<Rich<'a, char>>> {
-    events_parser().repeated().collect::<Vec<_>>()
+    events_parser()
+        .recover_with(via_parser(skip_definition()))
+        .repeated()
+        .collect::<Vec<_>>()
+        .then_ignore(padding())
+}
+
+/// A `use` of another script, e.g. `use utils::math;`.
+///   - Vec<String> `path`: The segments of the path, relative to the scripts directory
+///   - SimpleSpan `span`: Where the path was written, for error reporting
+#[derive(Debug, Clone, PartialEq)]
+pub struct Import {
+    pub path: Vec<String>,
+    pub span: SimpleSpan,
+}
+
+/// The `use` statements at the top of a script.
+pub fn imports_parser<'a>() -> impl Parser<'a, &'a str, Vec<Import>, Err<Rich<'a, char>>> {
+    text::keyword("use")
+        .padded_by(padding())
+        .ignore_then(
+            text::ident()
+                .map(str::to_string)
+                .separated_by(just("::"))
+                .at_least(1)
+                .collect::<Vec<_>>()
+                .map_with_span(|path, span| Import { path, span }),
+        )
+        .then_ignore(just(';').padded_by(padding()))
+        .repeated()
+        .collect()
+}
+
+/// The code lines of a script, one for each definition.
+pub type CodeLines<'a> = Vec<Vec<Option<Block<'a>>>>;
+
+/// A whole script: its imports, followed by its definitions.
+pub fn file_parser<'a>(
+) -> impl Parser<'a, &'a str, (Vec<Import>, CodeLines<'a>), Err<Rich<'a, char>>> {
+    imports_parser().then(parser())
 }
 
 pub fn actions_parser<'a>() -> impl Parser<'a, &'a str, Vec<Option<Block<'a>>>, Err<Rich<'a, char>>>
 {
-    let operation = {
-        just::<&str, &str, Err<Rich<'a, char>>>("=")
-            .or(just("+"))
-            .or(just("-"))
-            .or(just("*"))
-            .or(just("/"))
-            .or(just("%"))
-    };
-
     let recurs = recursive(|actions| {
         /*
         ACTIONS
@@ -40,90 +74,102 @@ pub fn actions_parser<'a>() -> impl Parser<'a, &'a str, Vec<Option<Block<'a>>>,
         let player_action = {
             text::keyword("player")
                 .ignore_then(just('.'))
-                .ignore_then(ident())
-                .then(argument_list())
-                .padded()
-                .map(|(f, datas): (String, Vec<ItemData>)| {
-                    let mut items: Vec<Item> = vec![];
-                    for (slot, data) in datas.into_iter().enumerate() {
-                        let id = data_to_id(&data);
-
-                        items.push(Item {
-                            id,
-                            slot: slot.try_into().expect("failed ot convert to usize"),
-                            item: data,
-                        })
-                    }
-                    vec![Some(Block::Code {
-                        block: "player_action",
-                        items,
-                        action: first_upper(&f),
-                        data: "",
-                        target: "Selection",
-                        inverted: "",
-                        sub_action: String::new(),
-                    })]
-                })
+                .ignore_then(ident().map_with_span(|name, span| (name, span)))
+                .then(tagged_argument_list())
+                .padded_by(padding())
+                .validate(
+                    |((f, span), args): ((String, SimpleSpan), Arguments), _, emitter| {
+                        let action = check_action("player_action", (&f, span), &args, emitter);
+                        let mut items: Vec<Item> = vec![];
+                        for (slot, data) in args.items.into_iter().enumerate() {
+                            let id = data_to_id(&data);
+
+                            items.push(Item {
+                                id,
+                                slot: slot.try_into().expect("failed ot convert to usize"),
+                                item: data,
+                            })
+                        }
+                        add_tags("player_action", &action, &mut items, args.tags, emitter);
+                        vec![Some(Block::Code {
+                            block: "player_action",
+                            items,
+                            action,
+                            data: "",
+                            target: "Selection",
+                            inverted: "",
+                            sub_action: String::new(),
+                        })]
+                    },
+                )
         }
         .boxed();
 
         let entity_action = {
             text::keyword("entity")
                 .ignore_then(just('.'))
-                .ignore_then(ident())
-                .then(argument_list())
-                .padded()
-                .map(|(f, datas): (String, Vec<ItemData>)| {
-                    let mut items: Vec<Item> = vec![];
-                    for (slot, data) in datas.into_iter().enumerate() {
-                        let id = data_to_id(&data);
-
-                        items.push(Item {
-                            id,
-                            slot: slot.try_into().expect("failed ot convert to usize"),
-                            item: data,
-                        })
-                    }
-                    vec![Some(Block::Code {
-                        block: "entity_action",
-                        items,
-                        action: first_upper(&f),
-                        data: "",
-                        target: "Selection",
-                        inverted: "",
-                        sub_action: String::new(),
-                    })]
-                })
+                .ignore_then(ident().map_with_span(|name, span| (name, span)))
+                .then(tagged_argument_list())
+                .padded_by(padding())
+                .validate(
+                    |((f, span), args): ((String, SimpleSpan), Arguments), _, emitter| {
+                        let action = check_action("entity_action", (&f, span), &args, emitter);
+                        let mut items: Vec<Item> = vec![];
+                        for (slot, data) in args.items.into_iter().enumerate() {
+                            let id = data_to_id(&data);
+
+                            items.push(Item {
+                                id,
+                                slot: slot.try_into().expect("failed ot convert to usize"),
+                                item: data,
+                            })
+                        }
+                        add_tags("entity_action", &action, &mut items, args.tags, emitter);
+                        vec![Some(Block::Code {
+                            block: "entity_action",
+                            items,
+                            action,
+                            data: "",
+                            target: "Selection",
+                            inverted: "",
+                            sub_action: String::new(),
+                        })]
+                    },
+                )
         }
         .boxed();
 
         let game_action = {
             text::keyword("plot")
                 .ignore_then(just('.'))
-                .ignore_then(ident())
-                .then(argument_list())
-                .padded()
-                .map(|(f, datas): (String, Vec<ItemData>)| {
-                    let mut items: Vec<Item> = vec![];
-                    for (slot, data) in datas.into_iter().enumerate() {
-                        let id = data_to_id(&data);
-
-                        items.push(Item {
-                            id,
-                            slot: slot.try_into().expect("failed to convert to usize"),
-                            item: data,
-                        })
-                    }
-                    vec![Some(Block::Code {
-                        block: "game_action",
-                        items,
-                        action: first_upper(&f),
-                        data: "",
-                        target: "Selection",
-                        inverted: "",
-                        sub_action: String::new(),
-                    })]
-                })
+                .ignore_then(ident().map_with_span(|name, span| (name, span)))
+                .then(tagged_argument_list())
+                .padded_by(padding())
+                .validate(
+                    |((f, span), args): ((String, SimpleSpan), Arguments), _, emitter| {
+                        let action = check_action("game_action", (&f, span), &args, emitter);
+                        let mut items: Vec<Item> = vec![];
+                        for (slot, data) in args.items.into_iter().enumerate() {
+                            let id = data_to_id(&data);
+
+                            items.push(Item {
+                                id,
+                                slot: slot.try_into().expect("failed to convert to usize"),
+                                item: data,
+                            })
+                        }
+                        add_tags("game_action", &action, &mut items, args.tags, emitter);
+                        vec![Some(Block::Code {
+                            block: "game_action",
+                            items,
+                            action,
+                            data: "",
+                            target: "Selection",
+                            inverted: "",
+                            sub_action: String::new(),
+                        })]
+                    },
+                )
         }
         .boxed();
 
@@ -132,42 +178,45 @@ pub fn actions_parser<'a>() -> impl Parser<'a, &'a str, Vec<Option<Block<'a>>>,
          */
         let set_variable = {
             text::keyword("var")
-                .padded()
-                .ignore_then(variable_parser())
-                .padded()
-                .then(operation)
-                .padded()
-                .then(ident())
-                .padded()
-                .then(argument_list())
-                .map(
-                    |(((var, op), effect), args): (((ItemData, &str), String), Vec<ItemData>)| {
+                .padded_by(padding())
+                .ignore_then(variable_parser().map_with_span(|var, span| (var, span)))
+                .padded_by(padding())
+                .then(operation())
+                .padded_by(padding())
+                // A literal like `vec(1, 2, 3)` is a value to assign, which `set_expression` reads.
+                .then(
+                    ident()
+                        .filter(|effect: &String| !LITERALS.contains(&effect.as_str()))
+                        .map_with_span(|effect, span| (effect, span)),
+                )
+                .padded_by(padding())
+                .then(tagged_argument_list())
+                .validate(
+                    |((((var, var_span), op), (effect, span)), mut args), _, emitter| {
+                        let mut tmp_effect = effect;
+                        if tmp_effect == "with" {
+                            tmp_effect = match op {
+                                "*" => "x".to_string(),
+                                _ => op.to_string(),
+                            };
+                        }
+                        args.items.insert(0, var);
+                        args.spans.insert(0, var_span);
+                        let action = check_action("set_var", (&tmp_effect, span), &args, emitter);
                         let mut items: Vec<Item> = vec![];
-                        for (slot, data) in args.into_iter().enumerate() {
+                        for (slot, data) in args.items.into_iter().enumerate() {
                             let id = data_to_id(&data);
-                            let slot = slot + 1;
                             items.push(Item {
                                 id,
                                 slot: slot.try_into().expect("failed ot convert to usize"),
                                 item: data,
                             })
                         }
-                        items.insert(
-                            0,
-                            Item {
-                                slot: 0,
-                                id: "var".to_string(),
-                                item: var,
-                            },
-                        );
-                        let mut tmp_effect = effect;
-                        if tmp_effect == "with" {
-                            tmp_effect = op.to_string();
-                        }
+                        add_tags("set_var", &action, &mut items, args.tags, emitter);
                         vec![Some(Block::Code {
                             block: "set_var",
                             items,
-                            action: first_upper(&tmp_effect),
+                            action,
                             data: "",
                             target: "",
                             inverted: "",
@@ -178,231 +227,227 @@ pub fn actions_parser<'a>() -> impl Parser<'a, &'a str, Vec<Option<Block<'a>>>,
         }
         .boxed();
 
+        let set_expression = {
+            text::keyword("var")
+                .padded_by(padding())
+                .ignore_then(variable_parser())
+                .padded_by(padding())
+                .then_ignore(just('='))
+                .then(expression())
+                .validate(|(var, expression), _, emitter| {
+                    lower_assignment(var, &expression, emitter)
+                })
+        }
+        .boxed();
+
         /*
         IFS
          */
 
         let if_player = {
             text::keyword("if")
-                .ignore_then(just(' '))
-                .ignore_then(just('!'))
-                .ignore_then(text::keyword("player"))
-                .ignore_then(just('.'))
-                .ignore_then(ident())
-                .padded()
+                .padded_by(padding())
+                .ignore_then(inverted())
+                .then_ignore(text::keyword("player"))
+                .then_ignore(just('.'))
+                .then(ident().map_with_span(|name, span| (name, span)))
+                .padded_by(padding())
                 .then(
                     actions
                         .clone()
                         .separated_by(just(';'))
                         .allow_trailing()
                         .collect::<Vec<_>>()
-                        .padded()
+                        .padded_by(padding())
                         .delimited_by(just('{'), just('}'))
-                        .padded(),
+                        .padded_by(padding()),
                 )
-                .padded()
-                .map(|(name, args): (String, Vec<Vec<Option<Block>>>)| {
-                    let mut out = vec![];
-                    for block in args {
-                        for sub_block in block.into_iter().flatten() {
-                            out.append(&mut vec![Some(sub_block)]);
+                .padded_by(padding())
+                .validate(
+                    |((inverted, (name, span)), args): (_, Vec<Vec<Option<Block>>>), _, emitter| {
+                        let action = check_action(
+                            "if_player",
+                            (&name, span),
+                            &Arguments::default(),
+                            emitter,
+                        );
+                        let mut out = vec![];
+                        for block in args {
+                            for sub_block in block.into_iter().flatten() {
+                                out.append(&mut vec![Some(sub_block)]);
+                            }
                         }
-                    }
-                    out.insert(
-                        0,
-                        Some(Block::Code {
-                            block: "if_player",
-                            items: vec![],
-                            action: first_upper(&name),
-                            data: "",
-                            target: "Selection",
-                            inverted: "",
-                            sub_action: String::new(),
-                        }),
-                    );
-                    out.insert(
-                        1,
-                        Some(Block::Bracket {
-                            direct: BracketDirection::Open,
+                        out.insert(
+                            0,
+                            Some(Block::Code {
+                                block: "if_player",
+                                items: vec![],
+                                action,
+                                data: "",
+                                target: "Selection",
+                                inverted,
+                                sub_action: String::new(),
+                            }),
+                        );
+                        out.insert(
+                            1,
+                            Some(Block::Bracket {
+                                direct: BracketDirection::Open,
+                                typ: BracketType::Norm,
+                            }),
+                        );
+                        out.push(Some(Block::Bracket {
+                            direct: BracketDirection::Close,
                             typ: BracketType::Norm,
-                        }),
-                    );
-                    out.push(Some(Block::Bracket {
-                        direct: BracketDirection::Close,
-                        typ: BracketType::Norm,
-                    }));
-                    out
-                })
+                        }));
+                        out
+                    },
+                )
         }
         .boxed();
 
         let if_entity = {
             text::keyword("if")
-                .ignore_then(just(' '))
-                .ignore_then(text::keyword("entity"))
-                .ignore_then(just('.'))
-                .ignore_then(ident())
+                .padded_by(padding())
+                .ignore_then(inverted())
+                .then_ignore(text::keyword("entity"))
+                .then_ignore(just('.'))
+                .then(ident().map_with_span(|name, span| (name, span)))
+                .padded_by(padding())
                 .then(
                     actions
                         .clone()
                         .separated_by(just(';'))
                         .allow_trailing()
                         .collect::<Vec<_>>()
-                        .padded()
+                        .padded_by(padding())
                         .delimited_by(just('{'), just('}'))
-                        .padded(),
+                        .padded_by(padding()),
                 )
-                .padded()
-                .map(|(name, args): (String, Vec<Vec<Option<Block>>>)| {
-                    let mut out = vec![];
-                    for block in args {
-                        for sub_block in block.into_iter().flatten() {
-                            out.append(&mut vec![Some(sub_block)]);
+                .padded_by(padding())
+                .validate(
+                    |((inverted, (name, span)), args): (_, Vec<Vec<Option<Block>>>), _, emitter| {
+                        let action = check_action(
+                            "if_entity",
+                            (&name, span),
+                            &Arguments::default(),
+                            emitter,
+                        );
+                        let mut out = vec![];
+                        for block in args {
+                            for sub_block in block.into_iter().flatten() {
+                                out.append(&mut vec![Some(sub_block)]);
+                            }
                         }
-                    }
-                    out.insert(
-                        0,
-                        Some(Block::Code {
-                            block: "if_entity",
-                            items: vec![],
-                            action: first_upper(&name),
-                            data: "",
-                            target: "Selection",
-                            inverted: "",
-                            sub_action: String::new(),
-                        }),
-                    );
-                    out.insert(
-                        1,
-                        Some(Block::Bracket {
-                            direct: BracketDirection::Open,
+                        out.insert(
+                            0,
+                            Some(Block::Code {
+                                block: "if_entity",
+                                items: vec![],
+                                action,
+                                data: "",
+                                target: "Selection",
+                                inverted,
+                                sub_action: String::new(),
+                            }),
+                        );
+                        out.insert(
+                            1,
+                            Some(Block::Bracket {
+                                direct: BracketDirection::Open,
+                                typ: BracketType::Norm,
+                            }),
+                        );
+                        out.push(Some(Block::Bracket {
+                            direct: BracketDirection::Close,
                             typ: BracketType::Norm,
-                        }),
-                    );
-                    out.push(Some(Block::Bracket {
-                        direct: BracketDirection::Close,
-                        typ: BracketType::Norm,
-                    }));
-                    out
-                })
+                        }));
+                        out
+                    },
+                )
         }
         .boxed();
 
         let if_game = {
             text::keyword("if")
-                .ignore_then(just(' '))
-                .ignore_then(text::keyword("plot"))
-                .ignore_then(just('.'))
-                .ignore_then(ident())
+                .padded_by(padding())
+                .ignore_then(inverted())
+                .then_ignore(text::keyword("plot"))
+                .then_ignore(just('.'))
+                .then(ident().map_with_span(|name, span| (name, span)))
+                .padded_by(padding())
                 .then(
                     actions
                         .clone()
                         .separated_by(just(';'))
                         .allow_trailing()
                         .collect::<Vec<_>>()
-                        .padded()
+                        .padded_by(padding())
                         .delimited_by(just('{'), just('}'))
-                        .padded(),
+                        .padded_by(padding()),
                 )
-                .padded()
-                .map(|(name, args): (String, Vec<Vec<Option<Block>>>)| {
-                    let mut out = vec![];
-                    for block in args {
-                        for sub_block in block.into_iter().flatten() {
-                            out.append(&mut vec![Some(sub_block)]);
+                .padded_by(padding())
+                .validate(
+                    |((inverted, (name, span)), args): (_, Vec<Vec<Option<Block>>>), _, emitter| {
+                        let action =
+                            check_action("if_game", (&name, span), &Arguments::default(), emitter);
+                        let mut out = vec![];
+                        for block in args {
+                            for sub_block in block.into_iter().flatten() {
+                                out.append(&mut vec![Some(sub_block)]);
+                            }
                         }
-                    }
-                    out.insert(
-                        0,
-                        Some(Block::Code {
-                            block: "if_game",
-                            items: vec![],
-                            action: first_upper(&name),
-                            data: "",
-                            target: "Selection",
-                            inverted: "",
-                            sub_action: String::new(),
-                        }),
-                    );
-                    out.insert(
-                        1,
-                        Some(Block::Bracket {
-                            direct: BracketDirection::Open,
+                        out.insert(
+                            0,
+                            Some(Block::Code {
+                                block: "if_game",
+                                items: vec![],
+                                action,
+                                data: "",
+                                target: "Selection",
+                                inverted,
+                                sub_action: String::new(),
+                            }),
+                        );
+                        out.insert(
+                            1,
+                            Some(Block::Bracket {
+                                direct: BracketDirection::Open,
+                                typ: BracketType::Norm,
+                            }),
+                        );
+                        out.push(Some(Block::Bracket {
+                            direct: BracketDirection::Close,
                             typ: BracketType::Norm,
-                        }),
-                    );
-                    out.push(Some(Block::Bracket {
-                        direct: BracketDirection::Close,
-                        typ: BracketType::Norm,
-                    }));
-                    out
-                })
+                        }));
+                        out
+                    },
+                )
         }
         .boxed();
 
         let if_variable = {
             text::keyword("if")
-                .padded()
-                .ignore_then(text::keyword("var"))
-                .padded()
-                .ignore_then(variable_parser())
-                .padded()
-                .then(operation)
-                .padded()
-                .then(ident())
-                .padded()
-                .then(argument_list())
+                .padded_by(padding())
+                .ignore_then(variable_condition())
                 .then(
                     actions
                         .clone()
                         .separated_by(just(';'))
                         .allow_trailing()
                         .collect::<Vec<_>>()
-                        .padded()
+                        .padded_by(padding())
                         .delimited_by(just('{'), just('}'))
-                        .padded(),
+                        .padded_by(padding()),
                 )
-                .map(|((((variable, effect), name), item_args), args)| {
+                .map(|(condition, args): (_, Vec<Vec<Option<Block>>>)| {
                     let mut out = vec![];
                     for block in args {
                         for sub_block in block.into_iter().flatten() {
                             out.append(&mut vec![Some(sub_block)]);
                         }
                     }
-                    let mut items: Vec<Item> = vec![];
-                    for (slot, data) in item_args.into_iter().enumerate() {
-                        let id = data_to_id(&data);
-                        let slot = slot + 1;
-                        items.push(Item {
-                            id,
-                            slot: slot.try_into().expect("failed ot convert to usize"),
-                            item: data,
-                        })
-                    }
-                    items.insert(
-                        0,
-                        Item {
-                            slot: 0,
-                            id: "var".to_string(),
-                            item: variable,
-                        },
-                    );
-                    let mut tmp_effect = effect;
-                    if tmp_effect == "with" {
-                        tmp_effect = &name;
-                    }
-                    out.insert(
-                        0,
-                        Some(Block::Code {
-                            block: "if_var",
-                            items,
-                            action: first_upper(&tmp_effect),
-                            data: "",
-                            target: "",
-                            inverted: "",
-                            sub_action: String::new(),
-                        }),
-                    );
+                    out.insert(0, Some(condition));
                     out.insert(
                         1,
                         Some(Block::Bracket {
@@ -419,18 +464,152 @@ pub fn actions_parser<'a>() -> impl Parser<'a, &'a str, Vec<Option<Block<'a>>>,
         }
         .boxed();
 
+        /*
+        REPEATS
+         */
+        let repeat = {
+            text::keyword("repeat")
+                .padded_by(padding())
+                .ignore_then(ident().map_with_span(|name, span| (name, span)))
+                .padded_by(padding())
+                .then(
+                    just("::")
+                        .padded_by(padding())
+                        .ignore_then(ident())
+                        .or_not(),
+                )
+                .padded_by(padding())
+                .then(tagged_argument_list().or_not())
+                .then(
+                    actions
+                        .clone()
+                        .separated_by(just(';'))
+                        .allow_trailing()
+                        .collect::<Vec<_>>()
+                        .padded_by(padding())
+                        .delimited_by(just('{'), just('}'))
+                        .padded_by(padding()),
+                )
+                .validate(
+                    |((((name, span), sub_action), item_args), args), _, emitter| {
+                        let mut out = vec![];
+                        for block in args {
+                            for sub_block in block.into_iter().flatten() {
+                                out.append(&mut vec![Some(sub_block)]);
+                            }
+                        }
+                        let item_args = item_args.unwrap_or_default();
+                        let action = check_action("repeat", (&name, span), &item_args, emitter);
+                        let mut items: Vec<Item> = vec![];
+                        for (slot, data) in item_args.items.into_iter().enumerate() {
+                            let id = data_to_id(&data);
+                            items.push(Item {
+                                id,
+                                slot: slot.try_into().expect("failed to convert to usize"),
+                                item: data,
+                            })
+                        }
+                        add_tags("repeat", &action, &mut items, item_args.tags, emitter);
+                        out.insert(
+                            0,
+                            Some(Block::Code {
+                                block: "repeat",
+                                items,
+                                action,
+                                data: "",
+                                target: "",
+                                inverted: "",
+                                sub_action: sub_action.map(|s| first_upper(&s)).unwrap_or_default(),
+                            }),
+                        );
+                        out.insert(
+                            1,
+                            Some(Block::Bracket {
+                                direct: BracketDirection::Open,
+                                typ: BracketType::Repeat,
+                            }),
+                        );
+                        out.push(Some(Block::Bracket {
+                            direct: BracketDirection::Close,
+                            typ: BracketType::Repeat,
+                        }));
+                        out
+                    },
+                )
+        }
+        .boxed();
+
+        /*
+        CALLS
+         */
+        let call_function = {
+            text::keyword("call")
+                .padded_by(padding())
+                .ignore_then(callee().map_with_span(|name, span| (name, span)))
+                .padded_by(padding())
+                .then(argument_list())
+                .map(
+                    |((name, span), args): ((String, SimpleSpan), Vec<ItemData>)| {
+                        vec![
+                            parameter_list(args),
+                            Some(Block::FunctionCall {
+                                block: "call_func",
+                                data: name,
+                                span,
+                            }),
+                        ]
+                    },
+                )
+        }
+        .boxed();
+
+        let start_process = {
+            text::keyword("start")
+                .padded_by(padding())
+                .ignore_then(callee().map_with_span(|name, span| (name, span)))
+                .padded_by(padding())
+                .then(tagged_argument_list())
+                .validate(
+                    |((name, span), args): ((String, SimpleSpan), Arguments), _, emitter| {
+                        // The given tags come after the defaults, so they override them.
+                        let mut tags = START_PROCESS_DEFAULTS
+                            .iter()
+                            .map(|(tag, option)| TagArgument {
+                                name: tag.to_string(),
+                                option: option.to_string(),
+                                span,
+                            })
+                            .collect::<Vec<_>>();
+                        tags.extend(args.tags);
+                        let mut items = vec![];
+                        add_tags("start_process", "dynamic", &mut items, tags, emitter);
+                        vec![
+                            parameter_list(args.items),
+                            Some(Block::ProcessCall {
+                                block: "start_process",
+                                data: name,
+                                span,
+                                items,
+                            }),
+                        ]
+                    },
+                )
+        }
+        .boxed();
+
         let select_object = {
             text::keyword("select")
-                .padded()
+                .padded_by(padding())
                 .ignore_then(
                     ident()
-                        .padded()
+                        .map_with_span(|name, span| (name, span))
+                        .padded_by(padding())
                         .then_ignore(just("::"))
-                        .padded()
+                        .padded_by(padding())
                         .then(ident())
-                        .padded()
-                        .then(argument_list())
-                        .padded()
+                        .padded_by(padding())
+                        .then(tagged_argument_list())
+                        .padded_by(padding())
                         .separated_by(just("->"))
                         .collect::<Vec<_>>(),
                 )
@@ -440,18 +619,18 @@ pub fn actions_parser<'a>() -> impl Parser<'a, &'a str, Vec<Option<Block<'a>>>,
                         .separated_by(just(';'))
                         .allow_trailing()
                         .collect::<Vec<_>>()
-                        .padded()
+                        .padded_by(padding())
                         .delimited_by(just('{'), just('}'))
-                        .padded(),
+                        .padded_by(padding()),
                 )
-                .map(|(selections, codes)| {
+                .validate(|(selections, codes), _, emitter| {
                     let mut out = vec![];
                     for selection in selections {
-                        let ((t1, mut t2), args) = selection;
+                        let (((t1, span), mut t2), args) = selection;
+                        let action = check_action("select_obj", (&t1, span), &args, emitter);
                         let mut items: Vec<Item> = vec![];
-                        for (slot, data) in args.into_iter().enumerate() {
+                        for (slot, data) in args.items.into_iter().enumerate() {
                             let id = data_to_id(&data);
-                            let slot = slot + 1;
                             items.push(Item {
                                 id,
                                 slot: slot.try_into().expect("failed ot convert to usize"),
@@ -461,10 +640,11 @@ pub fn actions_parser<'a>() -> impl Parser<'a, &'a str, Vec<Option<Block<'a>>>,
                         if t2 == "nil" {
                             t2 = "".to_string();
                         }
+                        add_tags("select_obj", &action, &mut items, args.tags, emitter);
                         out.push(Some(Block::Code {
                             block: "select_obj",
                             items,
-                            action: first_upper(&t1),
+                            action,
                             data: "",
                             target: "",
                             inverted: "",
@@ -492,18 +672,19 @@ pub fn actions_parser<'a>() -> impl Parser<'a, &'a str, Vec<Option<Block<'a>>>,
 
         let _else = {
             text::keyword("else")
-                .padded()
+                .padded_by(padding())
                 .ignore_then(
                     actions
                         .clone()
-                        .padded()
+                        .padded_by(padding())
                         .separated_by(just(';'))
                         .allow_trailing()
                         .collect::<Vec<_>>()
-                        .padded()
+                        .padded_by(padding())
                         .delimited_by(just('{'), just('}'))
-                        .padded(),
-                ).map(|blocks| {
+                        .padded_by(padding()),
+                )
+                .map(|blocks| {
                     let mut out = vec![];
                     for block in blocks {
                         for sub_block in block.into_iter().flatten() {
@@ -535,7 +716,30 @@ pub fn actions_parser<'a>() -> impl Parser<'a, &'a str, Vec<Option<Block<'a>>>,
                     }));
                     out
                 })
-        }.boxed();
+        }
+        .boxed();
+        /*
+        TESTS
+         */
+        let assert = {
+            text::keyword("assert")
+                .padded_by(padding())
+                .ignore_then(choice((
+                    text::keyword("sent")
+                        .padded_by(padding())
+                        .ignore_then(
+                            parse_text()
+                                .padded_by(padding())
+                                .delimited_by(just('('), just(')')),
+                        )
+                        .map(|message| Assertion::Sent(text_data(message))),
+                    variable_condition().map(|condition| Assertion::Condition(Box::new(condition))),
+                )))
+                .map_with_span(|assertion, span| vec![Some(Block::Assert { assertion, span })])
+                .padded_by(padding())
+        }
+        .boxed();
+
         /*
         OTHER
          */
@@ -544,13 +748,19 @@ pub fn actions_parser<'a>() -> impl Parser<'a, &'a str, Vec<Option<Block<'a>>>,
             entity_action,
             game_action,
             set_variable,
+            set_expression,
             if_player,
             if_entity,
             if_game,
             if_variable,
+            repeat,
+            call_function,
+            start_process,
             _else,
             select_object,
+            assert,
         ))
+        .recover_with(via_parser(skip_statement()))
     });
 
     recurs
@@ -558,25 +768,71 @@ pub fn actions_parser<'a>() -> impl Parser<'a, &'a str, Vec<Option<Block<'a>>>,
 
 pub fn events_parser<'a>() -> impl Parser<'a, &'a str, Vec<Option<Block<'a>>>, Err<Rich<'a, char>>>
 {
-    let player_event = text::keyword("event")
-        .padded()
-        .ignore_then(text::keyword("player"))
-        .padded()
-        .ignore_then(just('.'))
-        .padded()
-        .ignore_then(ident().padded())
-        .padded()
+    let event = text::keyword("event")
+        .padded_by(padding())
+        .ignore_then(choice((
+            text::keyword("player").to(("event", "player")),
+            text::keyword("entity").to(("entity_event", "entity")),
+        )))
+        .padded_by(padding())
+        .then_ignore(just('.'))
+        .padded_by(padding())
+        .then(ident().map_with_span(|name, span| (name, span)))
+        .validate(|((block, kind), (name, span)), _, emitter| {
+            let action = match ACTION_DUMP.find_action(block, &name) {
+                Some(action) => action.name.clone(),
+                None => {
+                    let mut message = format!("Unknown {kind} event `{name}`");
+                    if let Some(suggestion) = ACTION_DUMP.suggest_action(block, &name) {
+                        message.push_str(&format!(", did you mean `{}`?", suggestion.name));
+                    }
+                    emitter.emit(Rich::custom(span, message));
+                    first_upper(&name)
+                }
+            };
+            (block, action)
+        })
+        .padded_by(padding())
         .then(
             actions_parser()
                 .separated_by(just(';'))
                 .allow_trailing()
                 .collect::<Vec<_>>()
-                .padded()
-                .delimited_by(just('{'), just('}'))
-                .padded(),
+                .padded_by(padding())
+                .delimited_by(just('{'), just('}')),
+        )
+        .map(
+            |((block, action), args): ((&str, String), Vec<Vec<Option<Block>>>)| {
+                let mut out = vec![];
+                for block in args {
+                    for sub_block in block.into_iter().flatten() {
+                        out.append(&mut vec![Some(sub_block)]);
+                    }
+                }
+                out.insert(0, Some(Block::EventDefinition { block, action }));
+                out
+            },
+        );
+
+    let process = doc_comments()
+        .then(
+            text::keyword("proc")
+                .padded_by(padding())
+                .ignore_then(ident().map_with_span(|name, span| (name, span)))
+                .then_ignore(just('('))
+                .padded_by(padding())
+                .then_ignore(just(')'))
+                .padded_by(padding())
+                .then(
+                    actions_parser()
+                        .separated_by(just(';'))
+                        .allow_trailing()
+                        .collect::<Vec<_>>()
+                        .padded_by(padding())
+                        .delimited_by(just('{'), just('}')),
+                ),
         )
-        .padded()
-        .map(|(name, args): (String, Vec<Vec<Option<Block>>>)| {
+        .map(|(docs, ((name, span), args))| {
             let mut out = vec![];
             for block in args {
                 for sub_block in block.into_iter().flatten() {
@@ -585,32 +841,35 @@ pub fn events_parser<'a>() -> impl Parser<'a, &'a str, Vec<Option<Block<'a>>>, E
             }
             out.insert(
                 0,
-                Some(Block::EventDefinition {
-                    block: "event",
-                    action: first_upper(&name),
+                Some(Block::ProcessDefinition {
+                    block: "process",
+                    data: name,
+                    docs,
+                    span,
                 }),
             );
             out
         });
 
-    let process = text::keyword("proc")
-        .padded()
-        .ignore_then(ident())
-        .then_ignore(just('('))
-        .padded()
-        .then_ignore(just(')'))
-        .padded()
+    let function = doc_comments()
         .then(
-            actions_parser()
-                .separated_by(just(';'))
-                .allow_trailing()
-                .collect::<Vec<_>>()
-                .padded()
-                .delimited_by(just('{'), just('}'))
-                .padded(),
+            text::keyword("func")
+                .padded_by(padding())
+                .ignore_then(ident().map_with_span(|name, span| (name, span)))
+                .then_ignore(just('('))
+                .padded_by(padding())
+                .then_ignore(just(')'))
+                .padded_by(padding())
+                .then(
+                    actions_parser()
+                        .separated_by(just(';'))
+                        .allow_trailing()
+                        .collect::<Vec<_>>()
+                        .padded_by(padding())
+                        .delimited_by(just('{'), just('}')),
+                ),
         )
-        .padded()
-        .map(|(name, args): (String, Vec<Vec<Option<Block>>>)| {
+        .map(|(docs, ((name, span), args))| {
             let mut out = vec![];
             for block in args {
                 for sub_block in block.into_iter().flatten() {
@@ -619,32 +878,29 @@ pub fn events_parser<'a>() -> impl Parser<'a, &'a str, Vec<Option<Block<'a>>>, E
             }
             out.insert(
                 0,
-                Some(Block::ProcessDefinition {
-                    block: "process",
+                Some(Block::FunctionDefinition {
+                    block: "func",
                     data: name,
+                    docs,
+                    span,
                 }),
             );
             out
         });
 
-    let function = text::keyword("func")
-        .padded()
-        .ignore_then(ident())
-        .then_ignore(just('('))
-        .padded()
-        .then_ignore(just(')'))
-        .padded()
+    let test = text::keyword("test")
+        .padded_by(padding())
+        .ignore_then(parse_text().map_with_span(|name, span| (name, span)))
+        .padded_by(padding())
         .then(
             actions_parser()
                 .separated_by(just(';'))
                 .allow_trailing()
                 .collect::<Vec<_>>()
-                .padded()
-                .delimited_by(just('{'), just('}'))
-                .padded(),
+                .padded_by(padding())
+                .delimited_by(just('{'), just('}')),
         )
-        .padded()
-        .map(|(name, args): (String, Vec<Vec<Option<Block>>>)| {
+        .map(|((name, span), args)| {
             let mut out = vec![];
             for block in args {
                 for sub_block in block.into_iter().flatten() {
@@ -653,24 +909,414 @@ pub fn events_parser<'a>() -> impl Parser<'a, &'a str, Vec<Option<Block<'a>>>, E
             }
             out.insert(
                 0,
-                Some(Block::FunctionDefinition {
-                    block: "func",
-                    data: name,
+                Some(Block::TestDefinition {
+                    name: text_data(name),
+                    span,
                 }),
             );
             out
         });
 
-    choice((player_event, process, function))
+    choice((event, process, function, test))
+}
+
+/// The condition of an `if var` or an `assert`, e.g. `!var local.hp = with(0)`, as an If Variable block.
+fn variable_condition<'a>() -> impl Parser<'a, &'a str, Block<'a>, Err<Rich<'a, char>>> {
+    inverted()
+        .then_ignore(text::keyword("var"))
+        .padded_by(padding())
+        .then(variable_parser().map_with_span(|var, span| (var, span)))
+        .padded_by(padding())
+        .then(operation())
+        .padded_by(padding())
+        .then(ident().map_with_span(|name, span| (name, span)))
+        .padded_by(padding())
+        .then(tagged_argument_list())
+        .validate(
+            |((((inverted, (variable, var_span)), effect), (name, span)), mut item_args),
+             _,
+             emitter| {
+                let mut tmp_effect = name.as_str();
+                if tmp_effect == "with" {
+                    tmp_effect = effect;
+                }
+                item_args.items.insert(0, variable);
+                item_args.spans.insert(0, var_span);
+                let action = check_action("if_var", (tmp_effect, span), &item_args, emitter);
+                let mut items: Vec<Item> = vec![];
+                for (slot, data) in item_args.items.into_iter().enumerate() {
+                    let id = data_to_id(&data);
+                    items.push(Item {
+                        id,
+                        slot: slot.try_into().expect("failed ot convert to usize"),
+                        item: data,
+                    })
+                }
+                add_tags("if_var", &action, &mut items, item_args.tags, emitter);
+                Block::Code {
+                    block: "if_var",
+                    items,
+                    action,
+                    data: "",
+                    target: "",
+                    inverted,
+                    sub_action: String::new(),
+                }
+            },
+        )
+}
+
+/// The operator between a variable and the action applied to it, e.g. the `+` of `var local.x + with(1)`.
+fn operation<'a>() -> impl Parser<'a, &'a str, &'a str, Err<Rich<'a, char>>> + Clone {
+    just("=")
+        .or(just("+"))
+        .or(just("-"))
+        .or(just("*"))
+        .or(just("/"))
+        .or(just("%"))
+}
+
+/// The contents of a text literal.
+fn text_data(text: ItemData) -> String {
+    match text {
+        ItemData::Text { data } => data,
+        _ => String::new(),
+    }
+}
+
+/// Skips a broken statement, up to the `;` or `}` after it, so the statements after it are still checked.
+/// Its error is still reported, and it's replaced with nothing.
+fn skip_statement<'a>(
+) -> impl Parser<'a, &'a str, Vec<Option<Block<'a>>>, Err<Rich<'a, char>>> + Clone {
+    padding()
+        .ignore_then(
+            choice((delimited(), string_token(), none_of(";}").ignored()))
+                .repeated()
+                .at_least(1),
+        )
+        .map(|_| vec![])
+}
+
+/// Skips a broken definition, up to the next `event`, `func`, `proc` or `test`, so the definitions after it are still checked.
+/// Its error is still reported, and it's replaced with an empty code line.
+fn skip_definition<'a>(
+) -> impl Parser<'a, &'a str, Vec<Option<Block<'a>>>, Err<Rich<'a, char>>> + Clone {
+    let definition = choice((
+        text::keyword("event"),
+        text::keyword("func"),
+        text::keyword("proc"),
+        text::keyword("test"),
+        just("///"),
+    ));
+    padding()
+        .ignore_then(
+            choice((
+                delimited(),
+                string_token(),
+                just("//").then(none_of('\n').repeated()).ignored(),
+                // Whole identifiers, so the end of `prevent` isn't taken for an `event`.
+                text::ident().ignored(),
+                any().ignored(),
+            ))
+            .and_is(definition.not())
+            .repeated()
+            .at_least(1),
+        )
+        .map(|_| vec![])
+}
+
+/// A `{}` or `()` group and everything nested in it, for skipping over during recovery.
+fn delimited<'a>() -> impl Parser<'a, &'a str, (), Err<Rich<'a, char>>> + Clone {
+    recursive(|delimited| {
+        let inner = choice((delimited, string_token(), none_of("{}()\"").ignored())).repeated();
+        choice((
+            inner.clone().delimited_by(just('{'), just('}')),
+            inner.delimited_by(just('('), just(')')),
+        ))
+    })
+}
+
+/// A string, for skipping over during recovery, so the `;` or `}` in `"a;b"` isn't taken as the end of a statement.
+fn string_token<'a>() -> impl Parser<'a, &'a str, (), Err<Rich<'a, char>>> + Clone {
+    choice((just('\\').then(any()).ignored(), none_of("\"\n").ignored()))
+        .repeated()
+        .delimited_by(just('"'), just('"'))
+}
+
+/// The name of a called function or process, which can be qualified by the script defining it,
+/// e.g. `add` or `utils::math::add`.
+fn callee<'a>() -> impl Parser<'a, &'a str, String, Err<Rich<'a, char>>> + Clone {
+    text::ident()
+        .then_ignore(just("::"))
+        .repeated()
+        .slice()
+        .then(ident())
+        .map(|(qualifier, name): (&str, String)| format!("{qualifier}{name}"))
+}
+
+/// A block tag given by name at the end of an argument list, e.g. `alignment = "Centered"`.
+///   - String `name`: The name of the tag as written
+///   - String `option`: The option as written
+///   - SimpleSpan `span`: Where the tag was written, for error reporting
+#[derive(Debug, Clone)]
+pub struct TagArgument {
+    pub name: String,
+    pub option: String,
+    pub span: SimpleSpan,
+}
+
+/// The contents of a tagged argument list.
+///   - Vec<ItemData> `items`: The arguments, in order
+///   - Vec<SimpleSpan> `spans`: Where each argument was written, for error reporting
+///   - Vec<TagArgument> `tags`: The block tags given after the `;`
+#[derive(Debug, Clone, Default)]
+pub struct Arguments {
+    pub items: Vec<ItemData>,
+    pub spans: Vec<SimpleSpan>,
+    pub tags: Vec<TagArgument>,
+}
+
+/// An argument list that can end with block tags, separated from the arguments by a `;`.
+/// e.g. `("hi"; alignment = "Centered")`
+pub fn tagged_argument_list<'a>() -> impl Parser<'a, &'a str, Arguments, Err<Rich<'a, char>>> {
+    let tag = text::ident()
+        .then_ignore(just('=').padded_by(padding()))
+        .then(parse_text())
+        .map_with_span(|(name, option): (&str, ItemData), span| TagArgument {
+            name: name.to_string(),
+            option: match option {
+                ItemData::Text { data } => data,
+                _ => String::new(),
+            },
+            span,
+        });
+
+    arguments_parser()
+        .map_with_span(|data, span| (data, span))
+        .padded_by(padding())
+        .separated_by(just(','))
+        .collect::<Vec<(ItemData, SimpleSpan)>>()
+        .then(
+            just(';')
+                .ignore_then(
+                    tag.padded_by(padding())
+                        .separated_by(just(','))
+                        .collect::<Vec<_>>(),
+                )
+                .or_not(),
+        )
+        .padded_by(padding())
+        .delimited_by(just('('), just(')'))
+        .padded_by(padding())
+        .map(|(args, tags)| {
+            let (items, spans) = args.into_iter().unzip();
+            Arguments {
+                items,
+                spans,
+                tags: tags.unwrap_or_default(),
+            }
+        })
 }
 
 pub fn argument_list<'a>() -> impl Parser<'a, &'a str, Vec<ItemData>, Err<Rich<'a, char>>> {
     arguments_parser()
+        .padded_by(padding())
         .separated_by(just(','))
         .collect::<Vec<ItemData>>()
-        .padded()
+        .padded_by(padding())
         .delimited_by(just('('), just(')'))
-        .padded()
+        .padded_by(padding())
+}
+
+/// Parses the optional `!` in front of a condition.
+/// Outputs the value of the `inverted` field of the condition's block.
+fn inverted<'a>() -> impl Parser<'a, &'a str, &'a str, Err<Rich<'a, char>>> + Clone {
+    just('!')
+        .padded_by(padding())
+        .or_not()
+        .map(|not| if not.is_some() { "NOT" } else { "" })
+}
+
+/// The tag options `start` uses when it isn't given them, instead of DF's.
+/// A process started this way shares the locals and selection of the code starting it, like a function call.
+pub(crate) const START_PROCESS_DEFAULTS: [(&str, &str); 2] = [
+    ("Local Variables", "Share"),
+    ("Target Mode", "With current selection"),
+];
+
+/// Builds the `__FUNCTION_PARAMETERS` list passed to a called function or process.
+/// Returns None if there are no parameters to pass.
+fn parameter_list(args: Vec<ItemData>) -> Option<Block<'static>> {
+    if args.is_empty() {
+        return None;
+    }
+    let mut items = vec![Item {
+        id: "var".to_string(),
+        slot: 0,
+        item: ItemData::Variable {
+            scope: VariableScope::Local,
+            name: "__FUNCTION_PARAMETERS".to_string(),
+        },
+    }];
+    for (slot, data) in args.into_iter().enumerate() {
+        let id = data_to_id(&data);
+        let slot = slot + 1;
+        items.push(Item {
+            id,
+            slot: slot.try_into().expect("failed to convert to usize"),
+            item: data,
+        })
+    }
+    Some(Block::Code {
+        block: "set_var",
+        items,
+        action: "CreateList".to_string(),
+        data: "",
+        target: "",
+        inverted: "",
+        sub_action: String::new(),
+    })
+}
+
+/// Checks an action and its arguments against the action dump.
+/// Unknown actions, arguments of the wrong type and extra arguments are reported to the emitter.
+/// Returns the name of the action as DF expects it.
+fn check_action(
+    block: &str,
+    (name, span): (&str, SimpleSpan),
+    args: &Arguments,
+    emitter: &mut Emitter<Rich<char>>,
+) -> String {
+    let Some(found) = ACTION_DUMP.find_action(block, name) else {
+        let display = ACTION_DUMP
+            .codeblocks
+            .iter()
+            .find(|codeblock| codeblock.identifier == block)
+            .map(|codeblock| codeblock.name.to_lowercase())
+            .unwrap_or_else(|| block.to_string());
+        let mut message = if display.ends_with("action") {
+            format!("Unknown {display} `{name}`")
+        } else {
+            format!("Unknown {display} action `{name}`")
+        };
+        if let Some(suggestion) = ACTION_DUMP.suggest_action(block, name) {
+            message.push_str(&format!(", did you mean `{}`?", suggestion.name));
+        }
+        emitter.emit(Rich::custom(span, message));
+        return first_upper(name);
+    };
+
+    match found.check_arguments(&args.items) {
+        Ok(()) => {}
+        Err(ArgumentError::WrongType { index, expected }) => emitter.emit(Rich::custom(
+            args.spans.get(index).copied().unwrap_or(span),
+            format!(
+                "`{}` expects type `{}` here, found {}",
+                found.name,
+                expected.describe(),
+                args.items[index].describe()
+            ),
+        )),
+        Err(ArgumentError::TooMany { max }) => {
+            let extra = &args.spans[max..];
+            let span = match (extra.first(), extra.last()) {
+                (Some(first), Some(last)) => SimpleSpan::new(first.start, last.end),
+                _ => span,
+            };
+            emitter.emit(Rich::custom(
+                span,
+                format!(
+                    "`{}` takes at most {max} argument{}, found {}",
+                    found.name,
+                    if max == 1 { "" } else { "s" },
+                    args.items.len()
+                ),
+            ))
+        }
+    }
+    found.name.clone()
+}
+
+/// Adds the block tags of an action to its items.
+/// Tags that weren't given use their default option from the action dump.
+pub(crate) fn add_tags(
+    block: &str,
+    action: &str,
+    items: &mut Vec<Item>,
+    tags: Vec<TagArgument>,
+    emitter: &mut Emitter<Rich<char>>,
+) {
+    let Some(found) = ACTION_DUMP.find_action(block, action) else {
+        for given in tags {
+            emitter.emit(Rich::custom(
+                given.span,
+                format!("`{action}` has no tag `{}`", given.name),
+            ));
+        }
+        return;
+    };
+
+    // Blocks with a single action, like Start Process, call it `dynamic`, so the block is named instead.
+    let shown = if found.name == "dynamic" {
+        found
+            .codeblock_name
+            .split(' ')
+            .map(|word| {
+                let (first, rest) = word.split_at(1);
+                first.to_string() + &rest.to_lowercase()
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    } else {
+        found.name.clone()
+    };
+    let mut options = found
+        .tags
+        .iter()
+        .map(|tag| (tag, tag.default_option.as_str()))
+        .collect::<Vec<_>>();
+    for given in &tags {
+        let Some(tag) = found.find_tag(&given.name) else {
+            emitter.emit(Rich::custom(
+                given.span,
+                format!("`{shown}` has no tag `{}`", given.name),
+            ));
+            continue;
+        };
+        let Some(option) = tag.find_option(&given.option) else {
+            emitter.emit(Rich::custom(
+                given.span,
+                format!(
+                    "Invalid option for `{}`, expected one of: \"{}\"",
+                    tag.name,
+                    tag.options
+                        .iter()
+                        .map(|option| option.name.as_str())
+                        .collect::<Vec<_>>()
+                        .join("\", \"")
+                ),
+            ));
+            continue;
+        };
+        for (existing, selected) in options.iter_mut() {
+            if existing.name == tag.name {
+                *selected = option.name.as_str();
+            }
+        }
+    }
+
+    for (tag, option) in options {
+        items.push(Item {
+            id: "bl_tag".to_string(),
+            slot: tag.slot,
+            item: ItemData::Tag {
+                tag: tag.name.clone(),
+                option: option.to_string(),
+                action: found.name.clone(),
+                block: block.to_string(),
+            },
+        });
+    }
 }
 
 /// This matches an ItemData to it's ID in the item type.
@@ -678,7 +1324,7 @@ fn data_to_id(data: &ItemData) -> String {
     data.repr()
 }
 
-/// Converts the first letter of a slice to uppercase. 
+/// Converts the first letter of a slice to uppercase.
 /// Also converts it to a String struct.
 fn first_upper(s: &str) -> String {
     let mut c = s.chars();
@@ -687,3 +1333,401 @@ fn first_upper(s: &str) -> String {
         Some(f) => f.to_uppercase().collect::<String>() + c.as_str(),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parses a single statement and returns the JSON of its condition block.
+    fn condition_json(statement: &str) -> String {
+        let blocks = actions_parser()
+            .parse(statement)
+            .into_result()
+            .expect("failed to parse");
+        blocks
+            .into_iter()
+            .flatten()
+            .next()
+            .expect("no blocks")
+            .to_json()
+    }
+
+    /// Parses a single statement and returns the JSON of every block it makes.
+    fn blocks_json(statement: &str) -> Vec<String> {
+        actions_parser()
+            .parse(statement)
+            .into_result()
+            .expect("failed to parse")
+            .into_iter()
+            .flatten()
+            .map(|block| block.to_json())
+            .collect()
+    }
+
+    /// Parses a single statement and returns the messages of its errors.
+    fn error_messages(statement: &str) -> Vec<String> {
+        actions_parser()
+            .parse(statement)
+            .into_errors()
+            .into_iter()
+            .map(|error| error.reason().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn unknown_action_suggests_closest() {
+        assert_eq!(
+            error_messages("player.sendMesage(\"hi\")"),
+            vec!["Unknown player action `sendMesage`, did you mean `SendMessage`?"]
+        );
+    }
+
+    #[test]
+    fn wrong_argument_type() {
+        assert_eq!(
+            error_messages("player.giveItems(5)"),
+            vec!["`GiveItems` expects type `Item` here, found a Number"]
+        );
+    }
+
+    #[test]
+    fn too_many_arguments() {
+        assert_eq!(
+            error_messages("player.setHealth(5, 6)"),
+            vec!["`SetHealth` takes at most 1 argument, found 2"]
+        );
+    }
+
+    #[test]
+    fn unknown_sound_suggests_closest() {
+        assert_eq!(
+            error_messages("player.playSound(sound(\"Creper Deth\", 1, 2))"),
+            vec!["Unknown sound `Creper Deth`, did you mean `Creeper Death`?"]
+        );
+    }
+
+    #[test]
+    fn particle_fields_are_checked() {
+        assert_eq!(
+            error_messages("player.particle(particle(\"Cloud\"; size = 2))"),
+            vec!["`Cloud` has no field `size`, its fields are: Motion, Motion Variation"]
+        );
+    }
+
+    #[test]
+    fn game_prefix_is_always_a_variable() {
+        let json = condition_json("player.sendMessage(game.Location)");
+        assert!(
+            json.contains(r#"{"id":"var","data":{"name":"Location","scope":"unsaved"}}"#),
+            "{json}"
+        );
+    }
+
+    #[test]
+    fn game_values_have_their_own_prefix() {
+        let json = condition_json("player.sendMessage(value.Location, selection.CurrentHealth)");
+        assert!(
+            json.contains(r#"{"id":"g_val","data":{"type":"Location","target":"Default"}}"#),
+            "{json}"
+        );
+        assert!(
+            json.contains(
+                r#"{"id":"g_val","data":{"type":"Current Health","target":"Selection"}}"#
+            ),
+            "{json}"
+        );
+        assert_eq!(
+            error_messages("player.sendMessage(value.Nope)"),
+            vec!["Unknown game value `Nope`"]
+        );
+    }
+
+    #[test]
+    fn tags_are_filled_in_and_overridden() {
+        let tags = |alignment: &str| {
+            format!(
+                r#"{{"id":"bl_tag","data":{{"option":"{alignment}","tag":"Alignment Mode","action":"SendMessage","block":"player_action"}}}},"slot":26}},{{"item":{{"id":"bl_tag","data":{{"option":"Add spaces","tag":"Text Value Merging","action":"SendMessage","block":"player_action"}}}},"slot":25}}"#
+            )
+        };
+        let json = condition_json(r#"player.sendMessage("hi")"#);
+        assert!(json.contains(&tags("Regular")), "{json}");
+        let json = condition_json(r#"player.sendMessage("hi"; alignment = "Centered")"#);
+        assert!(json.contains(&tags("Centered")), "{json}");
+    }
+
+    #[test]
+    fn unknown_tags_and_options() {
+        assert_eq!(
+            error_messages(r#"player.sendMessage("hi"; nope = "x")"#),
+            vec!["`SendMessage` has no tag `nope`"]
+        );
+        assert_eq!(
+            error_messages(r#"player.sendMessage("hi"; alignment = "Sideways")"#),
+            vec![r#"Invalid option for `Alignment Mode`, expected one of: "Regular", "Centered""#]
+        );
+    }
+
+    #[test]
+    fn literals_are_assigned_not_called() {
+        for (literal, id) in [
+            ("vec(1, 2, 3)", "vec"),
+            (r#"sound("Creeper Death", 1, 2)"#, "snd"),
+            (r#"potion("Speed", 200, 1)"#, "pot"),
+            (r#"particle("Cloud")"#, "part"),
+            (r#"item("stone")"#, "item"),
+            ("items(stone; 3)", "item"),
+            ("loc(1, 2, 3)", "loc"),
+        ] {
+            let json = condition_json(&format!("var local.value = {literal}"));
+            assert!(json.contains(r#""block":"set_var""#), "{json}");
+            assert!(json.contains(r#""action":"=""#), "{json}");
+            assert!(
+                json.contains(&format!(r#"{{"item":{{"id":"{id}","#)),
+                "{json}"
+            );
+        }
+        // Anything else is still a Set Variable action.
+        let json = condition_json("var local.value = round(2)");
+        assert!(json.contains(r#""action":"Round""#), "{json}");
+    }
+
+    #[test]
+    fn text_escapes_and_interpolation() {
+        let json = condition_json(
+            r##"player.sendMessage("Hi {local.name}, \"\{\}\" \\ \n", r#"C:\{x}"#)"##,
+        );
+        assert!(
+            json.contains(r#""name":"Hi %var(name), \"{}\" \\ \n""#),
+            "{json}"
+        );
+        assert!(json.contains(r#""name":"C:\\{x}""#), "{json}");
+    }
+
+    #[test]
+    fn unterminated_string_points_at_quote() {
+        let errors = actions_parser()
+            .separated_by(just(';'))
+            .collect::<Vec<_>>()
+            .parse("player.sendMessage(\"hi);\nplayer.sendMessage(\"ok\")")
+            .into_errors();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(
+            errors[0].reason().to_string(),
+            "Unterminated string, this quote is never closed"
+        );
+        assert_eq!(errors[0].span().into_range(), 19..20);
+    }
+
+    #[test]
+    fn if_player_inverted() {
+        assert_eq!(
+            condition_json("if player.isSneaking {}"),
+            r#"{"id":"block","block":"if_player","args":{"items":[]},"action":"IsSneaking","target":"Selection","inverted":"","data":"","subAction":""}"#
+        );
+        assert_eq!(
+            condition_json("if !player.isSneaking {}"),
+            r#"{"id":"block","block":"if_player","args":{"items":[]},"action":"IsSneaking","target":"Selection","inverted":"NOT","data":"","subAction":""}"#
+        );
+    }
+
+    #[test]
+    fn if_entity_inverted() {
+        assert_eq!(
+            condition_json("if entity.isGrounded {}"),
+            r#"{"id":"block","block":"if_entity","args":{"items":[]},"action":"IsGrounded","target":"Selection","inverted":"","data":"","subAction":""}"#
+        );
+        assert_eq!(
+            condition_json("if ! entity.isGrounded {}"),
+            r#"{"id":"block","block":"if_entity","args":{"items":[]},"action":"IsGrounded","target":"Selection","inverted":"NOT","data":"","subAction":""}"#
+        );
+    }
+
+    #[test]
+    fn if_game_inverted() {
+        assert_eq!(
+            condition_json("if plot.hasPlayer {}"),
+            r#"{"id":"block","block":"if_game","args":{"items":[]},"action":"HasPlayer","target":"Selection","inverted":"","data":"","subAction":""}"#
+        );
+        assert_eq!(
+            condition_json("if !plot.hasPlayer {}"),
+            r#"{"id":"block","block":"if_game","args":{"items":[]},"action":"HasPlayer","target":"Selection","inverted":"NOT","data":"","subAction":""}"#
+        );
+    }
+
+    #[test]
+    fn if_var_inverted() {
+        assert_eq!(
+            condition_json("if var local.x = with(10) {}"),
+            r#"{"id":"block","block":"if_var","args":{"items":[{"item":{"id":"var","data":{"name":"x","scope":"local"}},"slot":0},{"item":{"id":"num","data":{"name":"10"}},"slot":1}]},"action":"=","target":"","inverted":"","data":"","subAction":""}"#
+        );
+        assert_eq!(
+            condition_json("if !var local.x = with(10) {}"),
+            r#"{"id":"block","block":"if_var","args":{"items":[{"item":{"id":"var","data":{"name":"x","scope":"local"}},"slot":0},{"item":{"id":"num","data":{"name":"10"}},"slot":1}]},"action":"=","target":"","inverted":"NOT","data":"","subAction":""}"#
+        );
+    }
+
+    #[test]
+    fn every_broken_statement_is_reported() {
+        let source = r#"event player.Join {
+    player.SendMesage("a; b");
+    player.SendMessage("fine");
+    player.Nope();
+}"#;
+        let (lines, errors) = parser().parse(source).into_output_errors();
+        let starts = errors.iter().map(|e| e.span().start).collect::<Vec<_>>();
+        assert_eq!(starts, vec![31, 94]);
+        let lines = lines.expect("the event was recovered");
+        let actions = lines[0]
+            .iter()
+            .flatten()
+            .filter(|block| block.to_json().contains("SendMessage"));
+        assert_eq!(actions.count(), 1);
+    }
+
+    #[test]
+    fn definitions_after_a_broken_one_are_parsed() {
+        let source = r#"evnt player.Quit {
+    player.SendMessage("bye");
+}
+
+func broken() {
+    player.SendMessage(1
+}
+
+func fine() {
+    player.SendMessage("hi");
+}"#;
+        let (lines, errors) = parser().parse(source).into_output_errors();
+        assert_eq!(errors.len(), 2);
+        let lines = lines.expect("the definitions were recovered");
+        let fine = lines.iter().find(|line| {
+            matches!(line.first(), Some(Some(block)) if block.to_json().contains("\"fine\""))
+        });
+        assert!(fine.is_some_and(|line| line.len() == 2));
+    }
+
+    #[test]
+    fn repeat_wraps_its_body_in_sticky_brackets() {
+        let blocks = blocks_json("repeat While::isSneaking() { player.sendMessage(\"hi\"); }");
+        assert_eq!(blocks.len(), 4);
+        assert_eq!(
+            blocks[0],
+            r#"{"id":"block","block":"repeat","args":{"items":[]},"action":"While","target":"","inverted":"","data":"","subAction":"IsSneaking"}"#
+        );
+        assert_eq!(
+            blocks[1],
+            r#"{"id":"bracket","direct":"open","type":"repeat"}"#
+        );
+        assert!(blocks[2].contains(r#""action":"SendMessage""#));
+        assert_eq!(
+            blocks[3],
+            r#"{"id":"bracket","direct":"close","type":"repeat"}"#
+        );
+
+        let blocks = blocks_json("repeat Multiple(5) {}");
+        assert_eq!(
+            blocks[0],
+            r#"{"id":"block","block":"repeat","args":{"items":[{"item":{"id":"num","data":{"name":"5"}},"slot":0}]},"action":"Multiple","target":"","inverted":"","data":"","subAction":""}"#
+        );
+        assert_eq!(blocks.len(), 3);
+    }
+
+    #[test]
+    fn call_passes_its_arguments_to_the_function() {
+        assert_eq!(
+            blocks_json("call utils::greet(1)"),
+            vec![
+                r#"{"id":"block","block":"set_var","args":{"items":[{"item":{"id":"var","data":{"name":"__FUNCTION_PARAMETERS","scope":"local"}},"slot":0},{"item":{"id":"num","data":{"name":"1"}},"slot":1}]},"action":"CreateList","target":"","inverted":"","data":"","subAction":""}"#,
+                r#"{"id":"block","block":"call_func","args":{"items":[]},"data":"utils::greet"}"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn start_fills_in_and_overrides_its_tags() {
+        let tags = |locals: &str, target: &str| {
+            format!(
+                r#"{{"id":"block","block":"start_process","args":{{"items":[{{"item":{{"id":"bl_tag","data":{{"option":"{target}","tag":"Target Mode","action":"dynamic","block":"start_process"}}}},"slot":26}},{{"item":{{"id":"bl_tag","data":{{"option":"{locals}","tag":"Local Variables","action":"dynamic","block":"start_process"}}}},"slot":25}}]}},"data":"loop"}}"#
+            )
+        };
+        assert_eq!(
+            blocks_json("start loop()"),
+            vec![tags("Share", "With current selection")]
+        );
+        assert_eq!(
+            blocks_json(
+                r#"start loop(; localVariables = "Copy", targetMode = "For each in selection")"#
+            ),
+            vec![tags("Copy", "For each in selection")]
+        );
+        assert_eq!(
+            error_messages(r#"start loop(; localVariables = "Nope")"#),
+            vec![
+                r#"Invalid option for `Local Variables`, expected one of: "Don't copy", "Copy", "Share""#
+            ]
+        );
+        assert_eq!(
+            error_messages(r#"start loop(; locals = "Copy")"#),
+            vec!["`Start Process` has no tag `locals`"]
+        );
+    }
+
+    #[test]
+    fn entity_events_are_definitions() {
+        let (lines, errors) = parser()
+            .parse("event entity.entityDmgEntity {}")
+            .into_output_errors();
+        assert!(errors.is_empty());
+        assert_eq!(
+            lines.unwrap()[0][0].as_ref().unwrap().to_json(),
+            r#"{"id":"block","block":"entity_event","args":{"items":[]},"action":"EntityDmgEntity"}"#
+        );
+    }
+
+    #[test]
+    fn unknown_event_suggests_closest() {
+        let errors = parser().parse("event player.Jion {}").into_errors();
+        assert_eq!(
+            errors
+                .iter()
+                .map(|e| e.reason().to_string())
+                .collect::<Vec<_>>(),
+            vec!["Unknown player event `Jion`, did you mean `Join`?"]
+        );
+        assert_eq!(errors[0].span().into_range(), 13..17);
+    }
+
+    #[test]
+    fn comments_are_skipped() {
+        let source = "// top\nevent player.Join { /* a /* nested */ still */ player.sendMessage(\"hi\"); // end\n}";
+        let (lines, errors) = parser().parse(source).into_output_errors();
+        assert!(errors.is_empty());
+        assert_eq!(lines.unwrap()[0].len(), 2);
+    }
+
+    #[test]
+    fn unterminated_comment_points_at_its_start() {
+        let errors = parser()
+            .parse("event player.Join { /* a /* b */\n player.sendMessage(\"hi\"); }")
+            .into_errors();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(
+            errors[0].reason().to_string(),
+            "Unterminated comment, this `/*` is never closed"
+        );
+        assert_eq!(errors[0].span().into_range(), 20..22);
+    }
+
+    #[test]
+    fn doc_comments_reach_the_definition() {
+        let (lines, errors) = parser()
+            .parse("/// Greets.\n///   Twice.\n//// not a doc\n// nor this\nfunc greet() {}")
+            .into_output_errors();
+        assert!(errors.is_empty());
+        let lines = lines.unwrap();
+        let Some(Block::FunctionDefinition { docs, .. }) = &lines[0][0] else {
+            panic!("expected a function definition");
+        };
+        assert_eq!(docs, &vec!["Greets.".to_string(), "  Twice.".to_string()]);
+    }
+}
@@ -0,0 +1,328 @@
+//! A client for the item API of the `recode` mod, which gives the player the templates it's sent.
+//!
+//! Every message is a line of JSON, and `recode` answers each one with a line of its own,
+//! `{"status":"success"}` or `{"status":"error","error":"..."}`.
+
+use std::{
+    fmt,
+    io::{self, BufRead, BufReader, Write},
+    net::{SocketAddr, TcpStream, ToSocketAddrs},
+    thread,
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+
+/// Where `recode` listens for items.
+pub const RECODE_ADDRESS: &str = "localhost:31372";
+
+/// How long to wait for `recode` to connect or answer.
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A client connected to `recode`, which keeps one connection open for every template it sends.
+///   - SocketAddr `address`: Where `recode` is
+///   - Option<BufReader<TcpStream>> `connection`: The open connection, if there is one
+///   - u32 `attempts`: How many times a template is tried before giving up
+///   - Duration `backoff`: How long to wait after the first failed attempt, doubling after each one after it
+pub struct RecodeClient {
+    address: SocketAddr,
+    connection: Option<BufReader<TcpStream>>,
+    attempts: u32,
+    backoff: Duration,
+}
+
+/// Why a template wasn't delivered.
+#[derive(Debug)]
+pub enum RecodeError {
+    /// `recode` couldn't be reached, or the connection broke, on every attempt.
+    Connection(io::Error),
+    /// The template was sent, but `recode` didn't answer. It isn't sent again, since it may have been delivered.
+    Unconfirmed(io::Error),
+    /// `recode` answered, but rejected the template.
+    Rejected(String),
+}
+
+/// Why an attempt at an exchange with `recode` failed.
+enum Attempt {
+    /// Nothing reached `recode`, so it can be tried again.
+    Retry(io::Error),
+    /// The message may have reached `recode`, so trying again could deliver it twice.
+    Stop(io::Error),
+}
+
+impl From<io::Error> for Attempt {
+    fn from(e: io::Error) -> Self {
+        Attempt::Retry(e)
+    }
+}
+
+impl fmt::Display for RecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecodeError::Connection(e) => write!(
+                f,
+                "couldn't reach `recode` ({e}), is Minecraft running with it installed?"
+            ),
+            RecodeError::Unconfirmed(e) => write!(
+                f,
+                "`recode` didn't answer ({e}), and it wasn't sent again in case it already was"
+            ),
+            RecodeError::Rejected(reason) => write!(f, "`recode` rejected it: {reason}"),
+        }
+    }
+}
+
+/// A message to `recode`. The `data` is itself JSON, holding the template item.
+#[derive(Serialize)]
+struct Message<'a> {
+    #[serde(rename = "type")]
+    kind: &'a str,
+    source: &'a str,
+    data: String,
+}
+
+/// The template item of a message.
+#[derive(Serialize)]
+struct TemplateItem<'a> {
+    name: &'a str,
+    data: &'a str,
+}
+
+/// What `recode` answers each message with.
+#[derive(Deserialize)]
+struct Reply {
+    status: String,
+    #[serde(default)]
+    error: Option<String>,
+}
+
+impl RecodeClient {
+    /// Connects to `recode`, retrying with backoff if it can't be reached yet.
+    pub fn connect(address: impl ToSocketAddrs) -> Result<RecodeClient, RecodeError> {
+        let address = address
+            .to_socket_addrs()
+            .map_err(RecodeError::Connection)?
+            .next()
+            .ok_or_else(|| {
+                RecodeError::Connection(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    "no address to connect to",
+                ))
+            })?;
+        let mut client = RecodeClient {
+            address,
+            connection: None,
+            attempts: 4,
+            backoff: Duration::from_millis(250),
+        };
+        client.retry(|client| Ok(client.connection().map(|_| ())?))?;
+        Ok(client)
+    }
+
+    /// Sends template data, named like the template item `recode` gives the player,
+    /// and waits for `recode` to accept it.
+    /// If it can't be written, it reconnects and sends it again.
+    /// Once it's written it's never sent again, even if no answer comes, since `recode` may have received it.
+    pub fn send(&mut self, name: &str, code: &str) -> Result<(), RecodeError> {
+        let data = serde_json::to_string(&TemplateItem { name, data: code })
+            .expect("failed to serialize template item");
+        let mut message = serde_json::to_string(&Message {
+            kind: "template",
+            source: "Blackstone",
+            data,
+        })
+        .expect("failed to serialize message");
+        message.push('\n');
+
+        let reply = self.retry(|client| {
+            let connection = client.connection()?;
+            connection.get_mut().write_all(message.as_bytes())?;
+            let mut reply = String::new();
+            match connection.read_line(&mut reply) {
+                Ok(0) => Err(Attempt::Stop(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "the connection was closed",
+                ))),
+                Ok(_) => Ok(reply),
+                Err(e) => Err(Attempt::Stop(e)),
+            }
+        })?;
+
+        match serde_json::from_str::<Reply>(&reply) {
+            Ok(reply) if reply.status == "success" => Ok(()),
+            Ok(reply) => Err(RecodeError::Rejected(reply.error.unwrap_or(reply.status))),
+            Err(_) => Err(RecodeError::Rejected(format!(
+                "unexpected reply `{}`",
+                reply.trim()
+            ))),
+        }
+    }
+
+    /// The open connection, connecting if there isn't one or `recode` closed it since it was last used.
+    fn connection(&mut self) -> io::Result<&mut BufReader<TcpStream>> {
+        if self
+            .connection
+            .as_ref()
+            .is_some_and(|c| is_closed(c.get_ref()))
+        {
+            self.connection = None;
+        }
+        if self.connection.is_none() {
+            let stream = TcpStream::connect_timeout(&self.address, TIMEOUT)?;
+            stream.set_read_timeout(Some(TIMEOUT))?;
+            stream.set_write_timeout(Some(TIMEOUT))?;
+            self.connection = Some(BufReader::new(stream));
+        }
+        Ok(self.connection.as_mut().expect("just connected"))
+    }
+
+    /// Runs an exchange with `recode` until it works, every attempt is used up, or it fails in a way that can't be retried.
+    /// After a failure the connection is dropped, so the next attempt starts on a fresh one.
+    fn retry<T>(
+        &mut self,
+        mut exchange: impl FnMut(&mut Self) -> Result<T, Attempt>,
+    ) -> Result<T, RecodeError> {
+        let mut backoff = self.backoff;
+        let mut attempt = 1;
+        loop {
+            match exchange(self) {
+                Ok(value) => return Ok(value),
+                Err(Attempt::Stop(e)) => {
+                    self.connection = None;
+                    return Err(RecodeError::Unconfirmed(e));
+                }
+                Err(Attempt::Retry(e)) => {
+                    self.connection = None;
+                    if attempt >= self.attempts {
+                        return Err(RecodeError::Connection(e));
+                    }
+                }
+            }
+            thread::sleep(backoff);
+            backoff *= 2;
+            attempt += 1;
+        }
+    }
+}
+
+/// Whether the other end closed a connection, without waiting for anything to arrive on it.
+fn is_closed(stream: &TcpStream) -> bool {
+    if stream.set_nonblocking(true).is_err() {
+        return true;
+    }
+    let closed = match stream.peek(&mut [0]) {
+        Ok(read) => read == 0,
+        Err(e) => e.kind() != io::ErrorKind::WouldBlock,
+    };
+    closed || stream.set_nonblocking(false).is_err()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    /// How the fake `recode` handles a message.
+    #[derive(Clone, Copy)]
+    enum Answer {
+        /// It answers the message.
+        Reply,
+        /// It answers the message, then closes the connection.
+        ReplyAndClose,
+        /// It reads the message, then hangs up without answering.
+        HangUp,
+    }
+
+    /// A fake `recode` that reads each message and handles it as `answers` says, returning the names it read.
+    fn fake_recode(answers: &'static [Answer]) -> (SocketAddr, thread::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let handle = thread::spawn(move || {
+            let mut received = vec![];
+            let mut answers = answers.iter();
+            for stream in listener.incoming() {
+                let mut reader = BufReader::new(stream.unwrap());
+                loop {
+                    let mut line = String::new();
+                    if reader.read_line(&mut line).unwrap_or(0) == 0 {
+                        break;
+                    }
+                    let Some(&answer) = answers.next() else {
+                        return received;
+                    };
+                    let message: serde_json::Value = serde_json::from_str(&line).unwrap();
+                    let item: serde_json::Value =
+                        serde_json::from_str(message["data"].as_str().unwrap()).unwrap();
+                    received.push(item["name"].as_str().unwrap().to_string());
+                    if let Answer::HangUp = answer {
+                        break;
+                    }
+                    let reply = if item["data"] == "bad" {
+                        r#"{"status":"error","error":"invalid template"}"#
+                    } else {
+                        r#"{"status":"success"}"#
+                    };
+                    writeln!(reader.get_mut(), "{reply}").unwrap();
+                    if answers.len() == 0 {
+                        return received;
+                    }
+                    if let Answer::ReplyAndClose = answer {
+                        break;
+                    }
+                }
+            }
+            received
+        });
+        (address, handle)
+    }
+
+    #[test]
+    fn templates_are_acknowledged_and_reconnected() {
+        let (address, recode) = fake_recode(&[Answer::ReplyAndClose, Answer::Reply, Answer::Reply]);
+        let mut client = RecodeClient::connect(address).unwrap();
+        client.backoff = Duration::from_millis(1);
+        client.send("Function » 'quoted' \"name\"", "H4sI").unwrap();
+        // The fake closes the connection after this one, so the next is sent on a new connection.
+        thread::sleep(Duration::from_millis(50));
+        client.send("Player Event » Join", "H4sI").unwrap();
+        assert!(matches!(
+            client.send("Process » broken", "bad"),
+            Err(RecodeError::Rejected(reason)) if reason == "invalid template"
+        ));
+        assert_eq!(
+            recode.join().unwrap(),
+            vec![
+                "Function » 'quoted' \"name\"",
+                "Player Event » Join",
+                "Process » broken"
+            ]
+        );
+    }
+
+    #[test]
+    fn unanswered_templates_are_not_sent_again() {
+        let (address, recode) = fake_recode(&[Answer::HangUp, Answer::Reply]);
+        let mut client = RecodeClient::connect(address).unwrap();
+        client.backoff = Duration::from_millis(1);
+        assert!(matches!(
+            client.send("Function » greet", "H4sI"),
+            Err(RecodeError::Unconfirmed(_))
+        ));
+        client.send("Function » leave", "H4sI").unwrap();
+        assert_eq!(
+            recode.join().unwrap(),
+            vec!["Function » greet", "Function » leave"]
+        );
+    }
+
+    #[test]
+    fn unreachable_recode_is_an_error() {
+        // Nothing listens on a port that was just freed.
+        let address = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let client = RecodeClient::connect(address);
+        assert!(matches!(client, Err(RecodeError::Connection(_))));
+    }
+}
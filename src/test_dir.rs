@@ -0,0 +1,44 @@
+//! A fresh directory for tests to write fixture files into, removed again once the test is done.
+
+use std::{
+    ops::Deref,
+    path::{Path, PathBuf},
+};
+
+/// A directory under the system's temp directory, named after the test using it and this process.
+/// It's created empty, and removed along with everything in it when dropped.
+pub struct TestDir(PathBuf);
+
+impl TestDir {
+    pub fn new(name: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!("blackstone-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        TestDir(dir)
+    }
+
+    /// Writes each file, creating the directories it's in.
+    pub fn with_files(name: &str, files: &[(&str, &str)]) -> Self {
+        let dir = Self::new(name);
+        for (path, contents) in files {
+            let path = dir.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, contents).unwrap();
+        }
+        dir
+    }
+}
+
+impl Deref for TestDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TestDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
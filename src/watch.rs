@@ -0,0 +1,166 @@
+//! `watch`: noticing which scripts changed, by polling when they were last modified,
+//! and keeping track of the code lines built from each script so only the ones that changed are sent.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use crate::output::BuiltLine;
+
+/// How often the scripts are checked for changes.
+pub const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// How long to wait before building and sending scripts again after they couldn't be, without them changing.
+pub const RETRY_INTERVAL: Duration = Duration::from_secs(5);
+
+/// What `watch` knows about the scripts it watches.
+///   - BTreeMap<PathBuf, SystemTime> `modified`: When each script was last modified, as of the last poll
+///   - BTreeSet<PathBuf> `pending`: The scripts that changed, but haven't been built and delivered since
+///   - BTreeMap<PathBuf, Vec<BuiltLine>> `built`: The code lines last delivered from each script
+#[derive(Debug, Default)]
+pub struct Watch {
+    modified: BTreeMap<PathBuf, SystemTime>,
+    pending: BTreeSet<PathBuf>,
+    built: BTreeMap<PathBuf, Vec<BuiltLine>>,
+}
+
+impl Watch {
+    /// Checks the scripts for changes since the last poll, returning the ones that were added, modified or removed.
+    /// Scripts that were watched before but aren't given anymore count as removed.
+    /// Every change is pending until `delivered` or `removed` says it's been dealt with.
+    pub fn poll<'a>(&mut self, paths: impl IntoIterator<Item = &'a Path>) -> Vec<PathBuf> {
+        let modified = paths
+            .into_iter()
+            .filter_map(|path| {
+                let modified = std::fs::metadata(path).and_then(|m| m.modified()).ok()?;
+                Some((path.to_path_buf(), modified))
+            })
+            .collect::<BTreeMap<_, _>>();
+        let changed = modified
+            .iter()
+            .filter(|(path, time)| self.modified.get(*path) != Some(time))
+            .map(|(path, _)| path)
+            .chain(
+                self.modified
+                    .keys()
+                    .filter(|path| !modified.contains_key(*path)),
+            )
+            .cloned()
+            .collect::<BTreeSet<_>>();
+        self.pending.extend(changed.iter().cloned());
+        self.modified = modified;
+        changed.into_iter().collect()
+    }
+
+    /// The scripts that changed and haven't been delivered since.
+    pub fn pending(&self) -> Vec<PathBuf> {
+        self.pending.iter().cloned().collect()
+    }
+
+    /// The code lines just built from a script that are new or different from the ones last delivered,
+    /// which are the only ones that need to be sent again.
+    pub fn changed(&self, path: &Path, lines: &[BuiltLine]) -> Vec<BuiltLine> {
+        let before = self.built.get(path).map(Vec::as_slice).unwrap_or_default();
+        lines
+            .iter()
+            .filter(|line| {
+                !before
+                    .iter()
+                    .any(|old| old.name == line.name && old.template == line.template)
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Records the code lines of a script once they've been delivered, so it's no longer pending.
+    pub fn delivered(&mut self, path: &Path, lines: Vec<BuiltLine>) {
+        self.pending.remove(path);
+        self.built.insert(path.to_path_buf(), lines);
+    }
+
+    /// Forgets a script that was removed, along with its code lines.
+    pub fn removed(&mut self, path: &Path) {
+        self.pending.remove(path);
+        self.built.remove(path);
+    }
+
+    /// Every code line delivered so far, with the path of the script it's from.
+    pub fn lines(&self) -> Vec<(&Path, &BuiltLine)> {
+        self.built
+            .iter()
+            .flat_map(|(path, lines)| lines.iter().map(move |line| (path.as_path(), line)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{codegen::block::Block, test_dir::TestDir};
+    use chumsky::span::SimpleSpan;
+
+    fn function(script: &str, line: usize, name: &str, calls: &str) -> BuiltLine {
+        BuiltLine::new(
+            script,
+            line,
+            &[
+                Block::FunctionDefinition {
+                    block: "func",
+                    data: name.to_string(),
+                    docs: vec![],
+                    span: SimpleSpan::new(0, 0),
+                },
+                Block::FunctionCall {
+                    block: "call_func",
+                    data: calls.to_string(),
+                    span: SimpleSpan::new(0, 0),
+                },
+            ],
+        )
+    }
+
+    #[test]
+    fn changes_are_pending_until_delivered() {
+        let dir = TestDir::with_files("watch", &[("a.bls", ""), ("b.bls", "")]);
+        let (a, b) = (dir.join("a.bls"), dir.join("b.bls"));
+
+        let mut watch = Watch::default();
+        assert_eq!(
+            watch.poll([a.as_path(), b.as_path()]),
+            vec![a.clone(), b.clone()]
+        );
+        assert!(watch.poll([a.as_path(), b.as_path()]).is_empty());
+        watch.delivered(&a, vec![]);
+        assert_eq!(watch.pending(), vec![b.clone()]);
+
+        std::fs::remove_file(&b).unwrap();
+        assert_eq!(watch.poll([a.as_path(), b.as_path()]), vec![b.clone()]);
+        watch.removed(&b);
+        assert!(watch.pending().is_empty());
+    }
+
+    #[test]
+    fn only_changed_lines_are_sent_again() {
+        let mut watch = Watch::default();
+        let path = Path::new("scripts/main.bls");
+        let first = vec![
+            function("main", 1, "greet", "wave"),
+            function("main", 2, "leave", "wave"),
+        ];
+        assert_eq!(watch.changed(path, &first), first);
+        watch.delivered(path, first);
+
+        let second = vec![
+            function("main", 1, "greet", "wave"),
+            function("main", 2, "leave", "bow"),
+        ];
+        assert_eq!(watch.changed(path, &second), vec![second[1].clone()]);
+        // Until they're delivered, they're still sent the next time.
+        assert_eq!(watch.changed(path, &second), vec![second[1].clone()]);
+        watch.delivered(path, second.clone());
+        assert!(watch.changed(path, &second).is_empty());
+        assert_eq!(watch.lines().len(), 2);
+    }
+}